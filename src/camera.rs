@@ -1,19 +1,60 @@
+use std::f32::consts::{FRAC_PI_2, PI};
+
 use crate::{
     geometry::ray::Ray,
     math::{
         mat4::Mat4f,
-        vec2::Vec2f,
+        vec2::{Vec2f, vec2},
         vec3::{Vec3f, vec3},
         vec4::vec4,
     },
+    projection::Projection,
+    scene::Scene,
 };
 
+#[derive(Clone, Copy)]
 pub(crate) struct Camera {
     pub(crate) look_from: Vec3f,
     pub(crate) look_at: Vec3f,
+    pub(crate) projection: Projection,
+    // Angle between the top and bottom screen edges in radians, for the perspective projection
+    pub(crate) vertical_fov: f32,
+    // Distance between the top and bottom screen edges, for the orthographic projection
+    pub(crate) view_height: f32,
+    // Rotation around the view direction in radians, counterclockwise as seen from the camera
+    pub(crate) roll: f32,
+    // Distance along the view direction before which nothing is seen
+    pub(crate) near_clip: f32,
+    // Radius of the lens disk that rays start from, 0 for a pinhole camera
+    pub(crate) aperture_radius: f32,
+    // Distance along the view direction at which everything is sharp
+    pub(crate) focus_distance: f32,
+    // Focus on whatever is in the screen center before every frame
+    pub(crate) has_autofocus: bool,
 }
 
 pub(crate) const WORLD_UP: Vec3f = vec3!(0.0, 1.0, 0.0);
+// Narrower views are hardly usable, and wider ones turn everything at the edges into streaks
+pub(crate) const MIN_FOV: f32 = PI / 36.0;
+pub(crate) const MAX_FOV: f32 = PI - PI / 18.0;
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            look_from: vec3!(0.0, 0.0, 2.0),
+            look_at: vec3!(0.0),
+            projection: Projection::Perspective,
+            // The viewport spans from -1 to 1 vertically at the distance of 1
+            vertical_fov: FRAC_PI_2,
+            view_height: 2.0,
+            roll: 0.0,
+            near_clip: 0.0,
+            aperture_radius: 0.0,
+            focus_distance: 2.0,
+            has_autofocus: false,
+        }
+    }
+}
 
 impl Camera {
     fn view_ray(&self) -> Ray {
@@ -23,11 +64,20 @@ impl Camera {
         }
     }
 
+    // Right, up, and forward directions of the camera in the world space
+    pub(crate) fn basis(&self) -> (Vec3f, Vec3f, Vec3f) {
+        let forward = self.view_ray().direction;
+        let level_right = forward.cross(WORLD_UP).normalize();
+        let level_up = level_right.cross(forward);
+        let (sin, cos) = self.roll.sin_cos();
+        let right = level_right * cos + level_up * sin;
+        let up = level_up * cos - level_right * sin;
+        (right, up, forward)
+    }
+
     fn camera_to_world(&self) -> Mat4f {
         let view_ray = self.view_ray();
-        let forward = view_ray.direction;
-        let right = forward.cross(WORLD_UP).normalize();
-        let up = right.cross(forward);
+        let (right, up, forward) = self.basis();
         Mat4f {
             x: vec4!(right, 0.0),
             y: vec4!(up, 0.0),
@@ -37,9 +87,33 @@ impl Camera {
         .transpose()
     }
 
+    // Viewport position goes from -1 to 1 vertically and is scaled to the field of view or the view
+    // height
     pub(crate) fn viewport_ray(&self, viewport_position: Vec2f) -> Ray {
-        let camera_origin = vec3!(0.0);
-        let camera_direction = vec3!(viewport_position, 1.0).normalize();
+        let (camera_origin, camera_direction) = match self.projection {
+            Projection::Perspective => {
+                let scale = (self.vertical_fov / 2.0).tan();
+                let direction = vec3!(scale * viewport_position, 1.0).normalize();
+                (vec3!(0.0), direction)
+            }
+            Projection::Orthographic => {
+                let scale = self.view_height / 2.0;
+                (vec3!(scale * viewport_position, 0.0), vec3!(0.0, 0.0, 1.0))
+            }
+        };
+        // Rays from across the lens meet again on the focus plane, so only what is there is sharp
+        let (camera_origin, camera_direction) = if self.aperture_radius > 0.0 {
+            let focus_point =
+                camera_origin + camera_direction * (self.focus_distance / camera_direction.z);
+            let lens_offset = self.aperture_radius * Vec2f::random_in_unit_disk();
+            let lens_point = camera_origin + vec3!(lens_offset, 0.0);
+            (lens_point, (focus_point - lens_point).normalize())
+        } else {
+            (camera_origin, camera_direction)
+        };
+        // Rays start on the near plane, so that whatever is in front of it is not hit
+        let camera_origin =
+            camera_origin + camera_direction * (self.near_clip / camera_direction.z);
         let camera_to_world = self.camera_to_world();
         Ray {
             origin: (camera_to_world * vec4!(camera_origin, 1.0)).xyz(),
@@ -48,4 +122,68 @@ impl Camera {
                 .normalize(),
         }
     }
+
+    // Position goes from the top left screen corner (0, 0) to the bottom right one (1, 1)
+    pub(crate) fn screen_ray(&self, position: Vec2f, aspect_ratio: f32) -> Ray {
+        let mut viewport_position = 2.0 * position - 1.0;
+        viewport_position.y = -viewport_position.y;
+        viewport_position.x *= aspect_ratio;
+        self.viewport_ray(viewport_position)
+    }
+
+    // Focuses on what the screen center shows, and keeps the focus when it shows nothing
+    pub(crate) fn autofocus(&mut self, scene: &Scene) {
+        let pinhole = Camera {
+            aperture_radius: 0.0,
+            ..*self
+        };
+        let center_ray = pinhole.viewport_ray(vec2!(0.0));
+        if let Some((intersection, _)) = scene.intersect(center_ray) {
+            let (_, _, forward) = self.basis();
+            let hit_position = center_ray.origin + center_ray.direction * intersection.distance;
+            self.focus_distance = (hit_position - self.look_from).dot(forward);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        color::Color,
+        geometry::aabb::Aabb,
+        material::Material,
+        math::{vec2::vec2, vec3::vec3},
+        object::Object,
+        scene::Scene,
+    };
+
+    use super::Camera;
+
+    #[test]
+    fn test() {
+        let scene = Scene::builder()
+            .spawn(Object {
+                material: Material::Lambertian { albedo: Color::RED },
+                intersect: Box::new(Aabb::centered(vec3!(0.0), 1.0)),
+            })
+            .build();
+        let mut camera = Camera {
+            look_from: vec3!(0.0, 0.0, 5.0),
+            look_at: vec3!(0.0),
+            ..Default::default()
+        };
+        let Some((intersection, _)) = scene.intersect(camera.viewport_ray(vec2!(0.0))) else {
+            panic!("box in front of the camera is expected to be hit");
+        };
+        assert!((intersection.distance - 4.0).abs() < 0.01);
+        assert!(intersection.normal == vec3!(0.0, 0.0, 1.0));
+        // The near plane cuts through the box, so its far face is seen from the inside
+        camera.near_clip = 5.0;
+        let ray = camera.viewport_ray(vec2!(0.0));
+        let Some((intersection, _)) = scene.intersect(ray) else {
+            panic!("box around the near plane is expected to be hit");
+        };
+        assert!((intersection.distance - 1.0).abs() < 0.01);
+        assert!(!intersection.is_front_face(ray));
+    }
 }
@@ -19,11 +19,12 @@ impl Color {
         Color(vec3!(r, g, b))
     }
 
-    pub(crate) fn encode(&self) -> String {
+    // Float to integer casts saturate, so out of range values are clamped
+    pub(crate) fn to_rgb8(self) -> [u8; 3] {
         let r = (MAX_VALUE * self.0.x) as u8;
         let g = (MAX_VALUE * self.0.y) as u8;
         let b = (MAX_VALUE * self.0.z) as u8;
-        format!(";2;{r};{g};{b}m")
+        [r, g, b]
     }
 
     pub(crate) fn inverted(&self) -> Self {
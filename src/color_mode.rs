@@ -0,0 +1,221 @@
+use std::{
+    env::var,
+    fmt::{Display, Formatter, Result},
+};
+
+use crate::{
+    color::Color,
+    math::{
+        vec2::Vec2u,
+        vec3::{Vec3f, vec3},
+    },
+};
+
+// https://en.wikipedia.org/wiki/Ordered_dithering
+//
+// Ordered dithering is used instead of error diffusion because it depends on the position only,
+// so a still image stays the same between frames and does not need to be redrawn.
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+// https://en.wikipedia.org/wiki/ANSI_escape_code#3-bit_and_4-bit
+const PALETTE_16: [u32; 16] = [
+    0x000000, 0xcd0000, 0x00cd00, 0xcdcd00, 0x0000ee, 0xcd00cd, 0x00cdcd, 0xe5e5e5, 0x7f7f7f,
+    0xff0000, 0x00ff00, 0xffff00, 0x5c5cff, 0xff00ff, 0x00ffff, 0xffffff,
+];
+
+// https://en.wikipedia.org/wiki/ANSI_escape_code#8-bit
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+const CUBE_START: u8 = 16;
+const GRAY_START: u8 = 232;
+const GRAY_COUNT: u8 = 24;
+
+#[derive(Clone, Copy)]
+pub(crate) enum ColorMode {
+    TrueColor,
+    Palette256,
+    Palette16,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum TerminalColor {
+    Rgb([u8; 3]),
+    Indexed256(u8),
+    Indexed16(u8),
+}
+
+impl ColorMode {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "truecolor" => Some(ColorMode::TrueColor),
+            "256" => Some(ColorMode::Palette256),
+            "16" => Some(ColorMode::Palette16),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ColorMode::TrueColor => "truecolor",
+            ColorMode::Palette256 => "256",
+            ColorMode::Palette16 => "16",
+        }
+    }
+
+    // https://github.com/termstandard/colors#checking-for-colorterm
+    //
+    // Most terminals support TrueColor without saying so in COLORTERM, so only the ones that
+    // advertise fewer colors in TERM are downgraded.
+    pub(crate) fn detect() -> Self {
+        let colorterm = var("COLORTERM").unwrap_or_default();
+        let term = var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorMode::TrueColor
+        } else if term.contains("256color") {
+            ColorMode::Palette256
+        } else if term == "linux" || term.ends_with("-16color") {
+            ColorMode::Palette16
+        } else {
+            ColorMode::TrueColor
+        }
+    }
+
+    // Dithering is only applied when a position is given
+    pub(crate) fn quantize(&self, color: Color, dither_position: Option<Vec2u>) -> TerminalColor {
+        // Zero-centered threshold in the range of (-0.5, 0.5)
+        let threshold = dither_position.map_or(0.0, |position| {
+            (BAYER_4X4[position.y % 4][position.x % 4] as f32 + 0.5) / 16.0 - 0.5
+        });
+        match self {
+            ColorMode::TrueColor => TerminalColor::Rgb(color.to_rgb8()),
+            ColorMode::Palette256 => {
+                // Spread the threshold over the distance between the cube levels
+                let color = color.0 + threshold / (CUBE_LEVELS.len() - 1) as f32;
+                TerminalColor::Indexed256(nearest_256(color))
+            }
+            ColorMode::Palette16 => {
+                // Palette colors are far from each other, so the spread is wide too
+                let color = color.0 + threshold * 0.5;
+                TerminalColor::Indexed16(nearest_16(color))
+            }
+        }
+    }
+}
+
+impl Display for ColorMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str(self.name())
+    }
+}
+
+impl TerminalColor {
+    // SGR parameters for the foreground or the background
+    pub(crate) fn write_parameters(&self, f: &mut Formatter<'_>, is_background: bool) -> Result {
+        match *self {
+            TerminalColor::Rgb([r, g, b]) => {
+                let layer = if is_background { 48 } else { 38 };
+                f.write_fmt(format_args!("{layer};2;{r};{g};{b}"))
+            }
+            TerminalColor::Indexed256(index) => {
+                let layer = if is_background { 48 } else { 38 };
+                f.write_fmt(format_args!("{layer};5;{index}"))
+            }
+            TerminalColor::Indexed16(index) => {
+                let base = if is_background { 40 } else { 30 };
+                // Bright colors have their own codes
+                let code = if index < 8 {
+                    base + index
+                } else {
+                    base + 60 + index - 8
+                };
+                f.write_fmt(format_args!("{code}"))
+            }
+        }
+    }
+}
+
+// The inverse of the 256-color quantization
+pub(crate) fn palette_256_rgb(index: u8) -> [u8; 3] {
+    if index < CUBE_START {
+        let [_, r, g, b] = PALETTE_16[index as usize].to_be_bytes();
+        [r, g, b]
+    } else if index < GRAY_START {
+        let index = (index - CUBE_START) as usize;
+        [index / 36, index / 6 % 6, index % 6].map(|level| CUBE_LEVELS[level])
+    } else {
+        [8 + 10 * (index - GRAY_START); 3]
+    }
+}
+
+fn nearest_256(color: Vec3f) -> u8 {
+    let cube_index = |value: f32| {
+        let value = (value * 255.0).clamp(0.0, 255.0);
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&index| (CUBE_LEVELS[index] as f32 - value).abs() as u32)
+            .unwrap_or_default()
+    };
+    let (r, g, b) = (
+        cube_index(color.x),
+        cube_index(color.y),
+        cube_index(color.z),
+    );
+    let cube_color = vec3!(
+        CUBE_LEVELS[r] as f32 / 255.0,
+        CUBE_LEVELS[g] as f32 / 255.0,
+        CUBE_LEVELS[b] as f32 / 255.0
+    );
+    // Grays go from 8 to 238 with the step of 10
+    let luminance = (color.x + color.y + color.z) / 3.0;
+    let gray_index = ((luminance * 255.0 - 8.0) / 10.0)
+        .round()
+        .clamp(0.0, (GRAY_COUNT - 1) as f32) as u8;
+    let gray = (8 + 10 * gray_index as u32) as f32 / 255.0;
+    let gray_color = vec3!(gray);
+    if distance_sqr(color, gray_color) < distance_sqr(color, cube_color) {
+        GRAY_START + gray_index
+    } else {
+        CUBE_START + (36 * r + 6 * g + b) as u8
+    }
+}
+
+fn nearest_16(color: Vec3f) -> u8 {
+    (0..PALETTE_16.len())
+        .min_by(|&a, &b| {
+            let a = distance_sqr(color, Color::from_hex(PALETTE_16[a]).0);
+            let b = distance_sqr(color, Color::from_hex(PALETTE_16[b]).0);
+            a.total_cmp(&b)
+        })
+        .unwrap_or_default() as u8
+}
+
+fn distance_sqr(a: Vec3f, b: Vec3f) -> f32 {
+    let delta = a - b;
+    delta.dot(delta)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        color::Color,
+        math::vec3::{Vec3f, vec3},
+    };
+
+    use super::{ColorMode, TerminalColor, nearest_256, palette_256_rgb};
+
+    #[test]
+    fn test() {
+        // Every cube and gray color is its own nearest one
+        for index in 16..=255 {
+            let [r, g, b] = palette_256_rgb(index);
+            let color = vec3!(r as f32, g as f32, b as f32) / 255.0;
+            assert_eq!(nearest_256(color), index);
+        }
+        // Cube levels 95 and 135 meet at 115
+        let color = |r: f32| -> Vec3f { vec3!(r / 255.0, 0.0, 1.0) };
+        assert_eq!(nearest_256(color(114.0)), 16 + 36 + 5);
+        assert_eq!(nearest_256(color(116.0)), 16 + 2 * 36 + 5);
+        let red = Color::from_hex(0xff0000);
+        assert!(ColorMode::TrueColor.quantize(red, None) == TerminalColor::Rgb([255, 0, 0]));
+        assert!(ColorMode::Palette256.quantize(red, None) == TerminalColor::Indexed256(196));
+        assert!(ColorMode::Palette16.quantize(red, None) == TerminalColor::Indexed16(9));
+    }
+}
@@ -1,33 +1,65 @@
 use std::fmt::{Display, Formatter, Result};
 
-use crate::Color;
+use crate::color_mode::TerminalColor;
 
 pub(crate) enum Escape {
     MakeCursorInvisible,
     MakeCursorVisible,
     MoveCursorToStart,
-    SetBackgroundColor(Color),
-    SetForegroundColor(Color),
+    ClearScreen,
+    // Zero-based position
+    MoveCursorTo { row: usize, column: usize },
+    SetBackgroundColor(TerminalColor),
+    SetForegroundColor(TerminalColor),
+    ResetAttributes,
+    EnterAlternateScreen,
+    LeaveAlternateScreen,
+    // Terminal presents everything between these at once
+    BeginSynchronizedUpdate,
+    EndSynchronizedUpdate,
+    // Presses, releases, drags, and the wheel are reported in the SGR format
+    EnableMouseTracking,
+    DisableMouseTracking,
 }
 
 // https://gist.github.com/fnky/458719343aabd01cfb17a3a4f7296797
 impl Escape {
     pub(crate) fn encode(&self) -> String {
-        let sequence = match self {
-            Escape::MakeCursorInvisible => "?25l".to_string(),
-            Escape::MakeCursorVisible => "?25h".to_string(),
-            Escape::MoveCursorToStart => "H".to_string(),
-            Escape::SetBackgroundColor(color) => format!("48{}", color.encode()),
-            Escape::SetForegroundColor(color) => format!("38{}", color.encode()),
-        };
-        format!("{ESCAPE}[{sequence}")
+        self.to_string()
     }
 }
 
 const ESCAPE: &str = "\x1b";
 
+// Sequences are written straight to the formatter, so a whole frame is encoded without
+// allocating a string per escape
 impl Display for Escape {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        f.write_str(&self.encode())
+        f.write_fmt(format_args!("{ESCAPE}["))?;
+        match self {
+            Escape::MakeCursorInvisible => f.write_str("?25l"),
+            Escape::MakeCursorVisible => f.write_str("?25h"),
+            Escape::MoveCursorToStart => f.write_str("H"),
+            Escape::ClearScreen => f.write_str("2J"),
+            Escape::MoveCursorTo { row, column } => {
+                f.write_fmt(format_args!("{};{}H", row + 1, column + 1))
+            }
+            Escape::SetBackgroundColor(color) => {
+                color.write_parameters(f, true)?;
+                f.write_str("m")
+            }
+            Escape::SetForegroundColor(color) => {
+                color.write_parameters(f, false)?;
+                f.write_str("m")
+            }
+            Escape::ResetAttributes => f.write_str("0m"),
+            Escape::EnterAlternateScreen => f.write_str("?1049h"),
+            Escape::LeaveAlternateScreen => f.write_str("?1049l"),
+            Escape::BeginSynchronizedUpdate => f.write_str("?2026h"),
+            Escape::EndSynchronizedUpdate => f.write_str("?2026l"),
+            // https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h2-Mouse-Tracking
+            Escape::EnableMouseTracking => f.write_str("?1000;1002;1006h"),
+            Escape::DisableMouseTracking => f.write_str("?1000;1002;1006l"),
+        }
     }
 }
@@ -4,7 +4,7 @@ use crate::{
     math::vec3::Vec3f,
 };
 
-#[derive(Default)]
+#[derive(Clone, Copy, Default)]
 pub(crate) struct Aabb {
     pub(crate) min: Vec3f,
     pub(crate) max: Vec3f,
@@ -22,25 +22,110 @@ impl Aabb {
         let max = self.max.max(other.max);
         Self { min, max }
     }
+
+    pub(crate) fn center(&self) -> Vec3f {
+        (self.min + self.max) * 0.5
+    }
+
+    pub(crate) fn surface_area(&self) -> f32 {
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
 }
 
-impl Intersect for Aabb {
-    fn intersect(&self, ray: Ray) -> Option<Intersection> {
+impl Aabb {
+    // Distance to where the ray enters the box, which is negative when it starts inside. It is
+    // enough to order and skip BVH nodes, unlike the intersection with the box as a shape.
+    pub(crate) fn entry_distance(&self, ray: Ray) -> Option<f32> {
+        let (fastest, slowest) = self.slab_times(ray);
+        let near = fastest.max_component();
+        let far = slowest.min_component();
+        (far > 0.0 && near < far).then_some(near - EPSILON)
+    }
+
+    // The times the ray needs to reach the min and max planes of every axis, sorted per axis
+    fn slab_times(&self, ray: Ray) -> (Vec3f, Vec3f) {
         // The time each component needs to walk a unit of distance
         let ray_delta = 1.0 / ray.direction;
         let time_to_min = ray_delta * (self.min - ray.origin);
         let time_to_max = ray_delta * (self.max - ray.origin);
-        let fastest = time_to_min.min(time_to_max);
-        let slowest = time_to_min.max(time_to_max);
+        (time_to_min.min(time_to_max), time_to_min.max(time_to_max))
+    }
+}
+
+impl Intersect for Aabb {
+    fn intersect(&self, ray: Ray) -> Option<Intersection> {
+        let (fastest, slowest) = self.slab_times(ray);
         // Near is the max fastest time because its point must be on the last intersected axis
         // plane. It is expected to be reached from outside of the box (from the inside, the value
         // is just negative). The opposite applies to the far which is on the first intersected
         // axis plane.
         let near = fastest.max_component();
         let far = slowest.min_component();
-        (far > 0.0 && near < far).then_some(Intersection {
-            distance: near - EPSILON,
-            normal: -ray.direction.signum() * fastest.step(near),
-        })
+        if far <= 0.0 || near >= far {
+            return None;
+        }
+        // From the inside, only the far intersection point is ahead of the origin, and the ray
+        // leaves through a face that looks the same way as it goes
+        let intersection = if near < 0.0 {
+            Intersection {
+                distance: far - EPSILON,
+                normal: ray.direction.signum() * (-slowest).step(-far),
+            }
+        } else {
+            Intersection {
+                distance: near - EPSILON,
+                normal: -ray.direction.signum() * fastest.step(near),
+            }
+        };
+        Some(intersection)
+    }
+
+    fn aabb(&self) -> Aabb {
+        *self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        geometry::{intersect::Intersect, ray::Ray},
+        math::vec3::vec3,
+    };
+
+    use super::Aabb;
+
+    #[test]
+    fn test() {
+        let aabb = Aabb::centered(vec3!(0.0), 1.0);
+        let ray = Ray {
+            origin: vec3!(0.0, 0.0, 3.0),
+            direction: vec3!(0.0, 0.0, -1.0),
+        };
+        let Some(intersection) = aabb.intersect(ray) else {
+            panic!("ray from outside is expected to hit");
+        };
+        assert!((intersection.distance - 2.0).abs() < 0.01);
+        assert!(intersection.normal == vec3!(0.0, 0.0, 1.0));
+        // From the inside the far face is hit, and its normal still looks outside
+        let ray = Ray {
+            origin: vec3!(0.0, 0.5, 0.0),
+            direction: vec3!(0.0, 0.0, -1.0),
+        };
+        let Some(intersection) = aabb.intersect(ray) else {
+            panic!("ray from inside is expected to hit");
+        };
+        assert!((intersection.distance - 1.0).abs() < 0.01);
+        assert!(intersection.normal == vec3!(0.0, 0.0, -1.0));
+        assert!(!intersection.is_front_face(ray));
+        assert!(
+            aabb.entry_distance(ray)
+                .is_some_and(|distance| distance < 0.0)
+        );
+        let ray = Ray {
+            origin: vec3!(0.0, 0.0, 3.0),
+            direction: vec3!(0.0, 0.0, 1.0),
+        };
+        assert!(aabb.intersect(ray).is_none());
     }
 }
@@ -0,0 +1,250 @@
+use crate::{
+    consts::EPSILON,
+    geometry::{aabb::Aabb, intersection::Intersection, ray::Ray},
+};
+
+// Relative costs for the surface area heuristic: visiting a node is cheaper than testing a
+// primitive, so a split only pays off when it saves enough primitive tests
+const TRAVERSAL_COST: f32 = 0.125;
+const INTERSECTION_COST: f32 = 1.0;
+const MAX_LEAF_SIZE: usize = 4;
+
+enum Node {
+    Leaf {
+        aabb: Aabb,
+        start: usize,
+        count: usize,
+    },
+    Branch {
+        aabb: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn aabb(&self) -> &Aabb {
+        match self {
+            Node::Leaf { aabb, .. } | Node::Branch { aabb, .. } => aabb,
+        }
+    }
+}
+
+// Bounding volume hierarchy over primitives that are referred to by their indices
+pub(crate) struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    pub(crate) fn build(aabbs: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: vec![],
+            indices: (0..aabbs.len()).collect(),
+        };
+        if !aabbs.is_empty() {
+            bvh.build_node(aabbs, 0, aabbs.len());
+        }
+        bvh
+    }
+
+    pub(crate) fn aabb(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map(|node| *node.aabb())
+            .unwrap_or_default()
+    }
+
+    fn build_node(&mut self, aabbs: &[Aabb], start: usize, count: usize) -> usize {
+        let node_index = self.nodes.len();
+        let indices = &mut self.indices[start..start + count];
+        let aabb = indices
+            .iter()
+            .map(|&index| aabbs[index])
+            .reduce(Aabb::extended)
+            .unwrap_or_default();
+        // Pad the box so that flat primitives lying on an axis plane do not produce a box that
+        // rays slip through
+        let aabb = Aabb {
+            min: aabb.min - EPSILON,
+            max: aabb.max + EPSILON,
+        };
+        let leaf = Node::Leaf { aabb, start, count };
+        self.nodes.push(leaf);
+        if count == 1 {
+            return node_index;
+        }
+        // Primitives sharing a center can't be told apart by any split, and splitting them off
+        // one by one would recurse as deep as there are primitives
+        let center = aabbs[indices[0]].center();
+        if indices.iter().all(|&index| aabbs[index].center() == center) {
+            return node_index;
+        }
+
+        let leaf_cost = INTERSECTION_COST * count as f32;
+        let area = aabb.surface_area();
+        let mut best_split: Option<(usize, usize, f32)> = None;
+        let mut right_areas = vec![0.0; count];
+        for axis in 0..3 {
+            sort_by_center(indices, aabbs, axis);
+            // Sweep from the right to know the area of every suffix, then from the left to
+            // evaluate every split position in a single pass
+            let mut right_aabb: Option<Aabb> = None;
+            for i in (1..count).rev() {
+                let aabb = aabbs[indices[i]];
+                let extended = right_aabb.map_or(aabb, |right_aabb| right_aabb.extended(aabb));
+                right_areas[i] = extended.surface_area();
+                right_aabb = Some(extended);
+            }
+            let mut left_aabb: Option<Aabb> = None;
+            for i in 1..count {
+                let aabb = aabbs[indices[i - 1]];
+                let extended = left_aabb.map_or(aabb, |left_aabb| left_aabb.extended(aabb));
+                left_aabb = Some(extended);
+                let left_cost = extended.surface_area() * i as f32;
+                let right_cost = right_areas[i] * (count - i) as f32;
+                let cost = TRAVERSAL_COST + INTERSECTION_COST * (left_cost + right_cost) / area;
+                if best_split.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    best_split = Some((axis, i, cost));
+                }
+            }
+        }
+        let Some((axis, left_count, cost)) = best_split else {
+            return node_index;
+        };
+        if cost >= leaf_cost && count <= MAX_LEAF_SIZE {
+            return node_index;
+        }
+        sort_by_center(&mut self.indices[start..start + count], aabbs, axis);
+        let left = self.build_node(aabbs, start, left_count);
+        let right = self.build_node(aabbs, start + left_count, count - left_count);
+        self.nodes[node_index] = Node::Branch { aabb, left, right };
+        node_index
+    }
+
+    // Returns the closest intersection among the primitives along with the primitive index
+    pub(crate) fn intersect<F>(
+        &self,
+        ray: Ray,
+        intersect_primitive: F,
+    ) -> Option<(usize, Intersection)>
+    where
+        F: Fn(usize, Ray) -> Option<Intersection>,
+    {
+        let root = self.nodes.first()?;
+        let root_distance = root.aabb().entry_distance(ray)?;
+        let mut closest: Option<(usize, Intersection)> = None;
+        let mut stack = vec![(0, root_distance)];
+        while let Some((node_index, node_distance)) = stack.pop() {
+            if closest.is_some_and(|(_, closest)| closest.distance <= node_distance) {
+                continue;
+            }
+            match self.nodes[node_index] {
+                Node::Leaf { start, count, .. } => {
+                    for &index in &self.indices[start..start + count] {
+                        let Some(intersection) = intersect_primitive(index, ray) else {
+                            continue;
+                        };
+                        if closest.is_none_or(|(_, closest)| intersection < closest) {
+                            closest = Some((index, intersection));
+                        }
+                    }
+                }
+                Node::Branch { left, right, .. } => {
+                    let left_distance = self.nodes[left].aabb().entry_distance(ray);
+                    let right_distance = self.nodes[right].aabb().entry_distance(ray);
+                    let mut children = [(left, left_distance), (right, right_distance)];
+                    // Push the far child first to pop and visit the near one first
+                    if let (Some(left_distance), Some(right_distance)) =
+                        (left_distance, right_distance)
+                        && left_distance < right_distance
+                    {
+                        children.swap(0, 1);
+                    }
+                    for (child, distance) in children {
+                        if let Some(distance) = distance {
+                            stack.push((child, distance));
+                        }
+                    }
+                }
+            }
+        }
+        closest
+    }
+
+    // Returns on the first primitive intersected closer than the max distance, which is enough
+    // for occlusion testing
+    pub(crate) fn intersect_any<F>(
+        &self,
+        ray: Ray,
+        max_distance: f32,
+        intersect_primitive: F,
+    ) -> bool
+    where
+        F: Fn(usize, Ray) -> Option<Intersection>,
+    {
+        let mut stack = vec![];
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if node
+                .aabb()
+                .entry_distance(ray)
+                .is_none_or(|distance| distance >= max_distance)
+            {
+                continue;
+            }
+            match *node {
+                Node::Leaf { start, count, .. } => {
+                    let hit = self.indices[start..start + count].iter().any(|&index| {
+                        intersect_primitive(index, ray)
+                            .is_some_and(|intersection| intersection.distance < max_distance)
+                    });
+                    if hit {
+                        return true;
+                    }
+                }
+                Node::Branch { left, right, .. } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        false
+    }
+}
+
+fn sort_by_center(indices: &mut [usize], aabbs: &[Aabb], axis: usize) {
+    indices.sort_unstable_by(|&a, &b| {
+        let a = aabbs[a].center()[axis];
+        let b = aabbs[b].center()[axis];
+        a.total_cmp(&b)
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        geometry::{aabb::Aabb, intersect::Intersect, ray::Ray},
+        math::vec3::vec3,
+    };
+
+    use super::Bvh;
+
+    #[test]
+    fn test() {
+        // Coincident primitives stay in one leaf instead of being split off one by one
+        let aabbs = vec![Aabb::centered(vec3!(0.0), 1.0); 1000];
+        let bvh = Bvh::build(&aabbs);
+        assert_eq!(bvh.nodes.len(), 1);
+        let ray = Ray {
+            origin: vec3!(0.0, 0.0, 5.0),
+            direction: vec3!(0.0, 0.0, -1.0),
+        };
+        let hit = bvh.intersect(ray, |index, ray| aabbs[index].intersect(ray));
+        assert!(hit.is_some_and(|(_, intersection)| (intersection.distance - 4.0).abs() < 0.01));
+        assert!(bvh.intersect_any(ray, 10.0, |index, ray| aabbs[index].intersect(ray)));
+    }
+}
@@ -1,5 +1,7 @@
-use crate::geometry::{intersection::Intersection, ray::Ray};
+use crate::geometry::{aabb::Aabb, intersection::Intersection, ray::Ray};
 
 pub(crate) trait Intersect {
     fn intersect(&self, ray: Ray) -> Option<Intersection>;
+
+    fn aabb(&self) -> Aabb;
 }
@@ -13,6 +13,21 @@ impl Intersection {
         let distance = self.distance - EPSILON;
         ray.origin + distance * ray.direction
     }
+
+    // The normal always looks outside, so the ray is leaving the object when they point to the
+    // same side
+    pub(crate) fn is_front_face(&self, ray: Ray) -> bool {
+        ray.direction.dot(self.normal) < 0.0
+    }
+
+    // Unlike the hit position that steps back along the ray, the origin is pushed off the surface
+    // along the normal to the side the new direction goes to. It is done by twice the epsilon to
+    // get over the epsilon some shapes already subtract from the distance.
+    pub(crate) fn biased_origin(&self, ray: Ray, direction: Vec3f) -> Vec3f {
+        let surface_position = ray.origin + self.distance * ray.direction;
+        let side = direction.dot(self.normal).signum();
+        surface_position + self.normal * (side * 2.0 * EPSILON)
+    }
 }
 
 impl PartialEq for Intersection {
@@ -1,4 +1,5 @@
 pub(crate) mod aabb;
+pub(crate) mod bvh;
 pub(crate) mod intersect;
 pub(crate) mod intersection;
 pub(crate) mod ray;
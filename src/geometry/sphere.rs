@@ -1,5 +1,5 @@
 use crate::{
-    geometry::{intersect::Intersect, intersection::Intersection, ray::Ray},
+    geometry::{aabb::Aabb, intersect::Intersect, intersection::Intersection, ray::Ray},
     math::vec3::Vec3f,
 };
 
@@ -13,10 +13,11 @@ impl Intersect for Sphere {
         let origin_to_center = self.center - ray.origin;
         // Let "mid" to be the middle between the intersection points
         let origin_to_mid_len = origin_to_center.dot(ray.direction);
-        if origin_to_mid_len < 0.0 {
+        let origin_to_center_len = origin_to_center.length();
+        let is_inside = origin_to_center_len < self.radius;
+        if origin_to_mid_len < 0.0 && !is_inside {
             return None;
         }
-        let origin_to_center_len = origin_to_center.length();
         let center_to_mid_len_sqr =
             origin_to_center_len * origin_to_center_len - origin_to_mid_len * origin_to_mid_len;
         let radius_sqr = self.radius * self.radius;
@@ -24,7 +25,12 @@ impl Intersect for Sphere {
             return None;
         }
         let intersection_to_mid_distance = (radius_sqr - center_to_mid_len_sqr).sqrt();
-        let origin_to_intersection = origin_to_mid_len - intersection_to_mid_distance;
+        // From the inside, only the far intersection point is ahead of the origin
+        let origin_to_intersection = if is_inside {
+            origin_to_mid_len + intersection_to_mid_distance
+        } else {
+            origin_to_mid_len - intersection_to_mid_distance
+        };
         let intersection = ray.origin + ray.direction * origin_to_intersection;
         let center_to_intersection = intersection - self.center;
         Some(Intersection {
@@ -32,4 +38,8 @@ impl Intersect for Sphere {
             normal: center_to_intersection.normalize(),
         })
     }
+
+    fn aabb(&self) -> Aabb {
+        Aabb::centered(self.center, self.radius)
+    }
 }
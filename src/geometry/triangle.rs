@@ -1,7 +1,8 @@
 use crate::{
-    Aabb, Vec3f,
+    Aabb,
     consts::EPSILON,
     geometry::{intersect::Intersect, intersection::Intersection, ray::Ray},
+    math::vec3::Vec3f,
 };
 
 pub(crate) struct Triangle {
@@ -17,12 +18,6 @@ impl Triangle {
         let normal = compute_normal(a, b, c);
         Self { a, b, c, normal }
     }
-
-    pub(crate) fn aabb(&self) -> Aabb {
-        let min = self.a.min(self.b).min(self.c);
-        let max = self.a.max(self.b).max(self.c);
-        Aabb { min, max }
-    }
 }
 
 impl Intersect for Triangle {
@@ -51,6 +46,12 @@ impl Intersect for Triangle {
             normal: self.normal,
         })
     }
+
+    fn aabb(&self) -> Aabb {
+        let min = self.a.min(self.b).min(self.c);
+        let max = self.a.max(self.b).max(self.c);
+        Aabb { min, max }
+    }
 }
 
 fn compute_normal(a: Vec3f, b: Vec3f, c: Vec3f) -> Vec3f {
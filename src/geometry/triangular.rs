@@ -1,13 +1,11 @@
-use std::cmp::Ordering;
-
 use crate::{
-    Aabb, geometry::intersect::Intersect, geometry::intersection::Intersection, geometry::ray::Ray,
-    geometry::triangle::Triangle,
+    geometry::aabb::Aabb, geometry::bvh::Bvh, geometry::intersect::Intersect,
+    geometry::intersection::Intersection, geometry::ray::Ray, geometry::triangle::Triangle,
 };
 
 pub(crate) struct Triangular {
     triangles: Vec<Triangle>,
-    aabb: Aabb,
+    bvh: Bvh,
 }
 
 impl Triangular {
@@ -18,11 +16,13 @@ impl Triangular {
 
 impl Intersect for Triangular {
     fn intersect(&self, ray: Ray) -> Option<Intersection> {
-        self.aabb.intersect(ray)?;
-        self.triangles
-            .iter()
-            .flat_map(|triangle| triangle.intersect(ray))
-            .min_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+        self.bvh
+            .intersect(ray, |index, ray| self.triangles[index].intersect(ray))
+            .map(|(_, intersection)| intersection)
+    }
+
+    fn aabb(&self) -> Aabb {
+        self.bvh.aabb()
     }
 }
 
@@ -39,16 +39,65 @@ impl TriangularBuilder {
 
     pub(crate) fn build(self) -> Triangular {
         let triangles = self.triangles;
-        let triangle_count = triangles.len();
-        let aabb = match triangle_count {
-            0 => Aabb::default(),
-            1 => triangles[0].aabb(),
-            _ => triangles
+        let aabbs: Vec<_> = triangles.iter().map(Triangle::aabb).collect();
+        let bvh = Bvh::build(&aabbs);
+        Triangular { triangles, bvh }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+
+    use crate::{
+        geometry::{intersect::Intersect, ray::Ray, triangle::Triangle},
+        math::vec3::Vec3f,
+        util::assert_nearly_eq_f32,
+    };
+
+    use super::Triangular;
+
+    #[test]
+    fn test() {
+        let triangles: Vec<_> = (0..500)
+            .map(|_| {
+                let center = 2.0 * Vec3f::random_unit();
+                let a = center + 0.2 * Vec3f::random_unit();
+                let b = center + 0.2 * Vec3f::random_unit();
+                let c = center + 0.2 * Vec3f::random_unit();
+                [a, b, c]
+            })
+            .collect();
+        let mut builder = Triangular::builder();
+        for &[a, b, c] in &triangles {
+            builder = builder.add_triangle(Triangle::new(a, b, c));
+        }
+        let triangular = builder.build();
+        let triangles: Vec<_> = triangles
+            .into_iter()
+            .map(|[a, b, c]| Triangle::new(a, b, c))
+            .collect();
+        let mut hit_count = 0;
+        for _ in 0..1000 {
+            let origin = 4.0 * Vec3f::random_unit();
+            let target = 0.5 * Vec3f::random_unit();
+            let ray = Ray {
+                origin,
+                direction: (target - origin).normalize(),
+            };
+            let brute_force = triangles
                 .iter()
-                .map(|triangle| triangle.aabb())
-                .reduce(|acc, e| acc.extended(e))
-                .unwrap_or_default(),
-        };
-        Triangular { triangles, aabb }
+                .flat_map(|triangle| triangle.intersect(ray))
+                .min_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            match (triangular.intersect(ray), brute_force) {
+                (Some(a), Some(b)) => {
+                    assert!(assert_nearly_eq_f32(a.distance, b.distance));
+                    hit_count += 1;
+                }
+                (None, None) => (),
+                _ => panic!("BVH and brute force disagree on hit"),
+            }
+        }
+        assert!(hit_count > 0);
     }
 }
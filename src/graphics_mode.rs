@@ -0,0 +1,85 @@
+use std::fmt::{Display, Formatter, Result};
+
+use crate::terminal;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum GraphicsMode {
+    // Every symbol is a terminal cell colored with escape codes
+    #[default]
+    Cells,
+    // The frame is rendered at pixel resolution and drawn as a Sixel image
+    // https://en.wikipedia.org/wiki/Sixel
+    Sixel,
+    // The frame is rendered at pixel resolution and sent with the kitty graphics protocol
+    // https://sw.kovidgoyal.net/kitty/graphics-protocol/
+    Kitty,
+}
+
+impl GraphicsMode {
+    pub(crate) const ALL: [GraphicsMode; 3] = [
+        GraphicsMode::Cells,
+        GraphicsMode::Sixel,
+        GraphicsMode::Kitty,
+    ];
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cells" => Some(GraphicsMode::Cells),
+            "sixel" => Some(GraphicsMode::Sixel),
+            "kitty" => Some(GraphicsMode::Kitty),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            GraphicsMode::Cells => "cells",
+            GraphicsMode::Sixel => "sixel",
+            GraphicsMode::Kitty => "kitty",
+        }
+    }
+
+    pub(crate) fn next(self) -> Self {
+        let index = GraphicsMode::ALL
+            .iter()
+            .position(|&mode| mode == self)
+            .unwrap_or_default();
+        GraphicsMode::ALL[(index + 1) % GraphicsMode::ALL.len()]
+    }
+
+    // Asks the terminal, so it must be called before the input starts reading
+    pub(crate) fn is_supported(&self) -> bool {
+        match self {
+            GraphicsMode::Cells => true,
+            // Primary device attributes list 4 when Sixel is available, e.g. "ESC [ ? 62 ; 4 c"
+            // https://vt100.net/docs/vt510-rm/DA1.html
+            GraphicsMode::Sixel => terminal::query("\x1b[c", b'c').is_some_and(|response| {
+                response
+                    .strip_prefix(b"\x1b[?")
+                    .and_then(|response| response.strip_suffix(b"c"))
+                    .is_some_and(|attributes| {
+                        attributes
+                            .split(|&byte| byte == b';')
+                            .any(|attribute| attribute == b"4")
+                    })
+            }),
+            // A query action is answered with OK, and the primary device attributes end the
+            // response, see is_synchronized_update_supported
+            // https://sw.kovidgoyal.net/kitty/graphics-protocol/#querying-support-and-available-transmission-mediums
+            GraphicsMode::Kitty => {
+                terminal::query("\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1b[c", b'c')
+                    .is_some_and(|response| {
+                        response
+                            .windows(b"i=31;OK".len())
+                            .any(|window| window == b"i=31;OK")
+                    })
+            }
+        }
+    }
+}
+
+impl Display for GraphicsMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{image::Image, math::vec2::Vec2u};
+
+// Terminals accept at most 4096 bytes of payload in every escape code
+const MAX_CHUNK_LEN: usize = 4096;
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// https://sw.kovidgoyal.net/kitty/graphics-protocol/
+//
+// Transmits raw RGB pixels and places them over the given number of cells at the cursor. The
+// image and its placement replace the previous ones with the same id instead of piling up. The
+// negative z-index puts the image under the text but over the cell backgrounds, and the cursor
+// is not moved.
+pub(crate) fn encode(image: &Image, id: u32, size_in_symbols: Vec2u) -> String {
+    let data = base64(&image.to_rgb8());
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(MAX_CHUNK_LEN).collect();
+    let mut kitty = String::with_capacity(data.len() + 32 * chunks.len());
+    for (index, chunk) in chunks.iter().enumerate() {
+        kitty.push_str("\x1b_G");
+        if index == 0 {
+            kitty.push_str(&format!(
+                "a=T,f=24,s={},v={},i={id},p=1,c={},r={},C=1,z=-1,q=2,",
+                image.size.x, image.size.y, size_in_symbols.x, size_in_symbols.y
+            ));
+        }
+        // Every chunk except the last one says that more data follows
+        let has_more = index + 1 < chunks.len();
+        kitty.push_str(if has_more { "m=1;" } else { "m=0;" });
+        // Base64 is ASCII
+        kitty.push_str(str::from_utf8(chunk).unwrap());
+        kitty.push_str("\x1b\\");
+    }
+    kitty
+}
+
+// Deletes the image with the given id and all of its placements
+pub(crate) fn delete(id: u32) -> String {
+    format!("\x1b_Ga=d,d=I,i={id},q=2\x1b\\")
+}
+
+// https://datatracker.ietf.org/doc/html/rfc4648#section-4
+fn base64(bytes: &[u8]) -> String {
+    let mut base64 = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16
+            | (chunk.get(1).copied().unwrap_or_default() as u32) << 8
+            | chunk.get(2).copied().unwrap_or_default() as u32;
+        // Every input byte covers one more output character, the rest is padding
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (group >> (18 - 6 * index) & 0x3f) as usize;
+                base64.push(BASE64_ALPHABET[sextet] as char);
+            } else {
+                base64.push('=');
+            }
+        }
+    }
+    base64
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{color::Color, image::Image, math::vec2::vec2};
+
+    use super::{base64, encode};
+
+    #[test]
+    fn test() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        // 3 bytes of every pixel become 4 characters, so 2048 pixels fill 2 chunks
+        let image = Image {
+            size: vec2!(64, 32),
+            pixels: vec![Color::BLACK; 2048],
+        };
+        let kitty = encode(&image, 7, vec2!(8, 4));
+        assert!(kitty.starts_with("\x1b_Ga=T,f=24,s=64,v=32,i=7,p=1,c=8,r=4,C=1,z=-1,q=2,m=1;"));
+        assert_eq!(kitty.matches("\x1b_G").count(), 2);
+        assert!(kitty.contains("\x1b\\\x1b_Gm=0;AAAA"));
+    }
+}
@@ -0,0 +1,84 @@
+pub(crate) mod kitty;
+pub(crate) mod png;
+pub(crate) mod ppm;
+pub(crate) mod sixel;
+
+use std::{io, path::Path, sync::Mutex};
+
+use crate::{
+    camera::Camera,
+    color::Color,
+    math::vec2::{Vec2f, Vec2u, vec2},
+    options::RenderOptions,
+    scene::Scene,
+    trace_stats::TraceStats,
+    util::{pcg, thread_pool::ThreadPool},
+};
+
+pub(crate) struct Image {
+    pub(crate) size: Vec2u,
+    pub(crate) pixels: Vec<Color>,
+}
+
+impl Image {
+    // Renders a single frame without a terminal
+    pub(crate) fn render(
+        scene: &Scene,
+        camera: &Camera,
+        options: &RenderOptions,
+        size: Vec2u,
+    ) -> Self {
+        let mut pool = ThreadPool::new(options.thread_count);
+        Self::render_with_stats(&mut pool, scene, camera, options, size, 0).0
+    }
+
+    pub(crate) fn render_with_stats(
+        pool: &mut ThreadPool,
+        scene: &Scene,
+        camera: &Camera,
+        options: &RenderOptions,
+        size: Vec2u,
+        frame_index: u64,
+    ) -> (Self, TraceStats) {
+        let mut pixels = vec![Color::default(); size.area()];
+        let aspect_ratio = size.x as f32 / size.y as f32;
+        let overall_stats = Mutex::new(TraceStats::default());
+        pool.run_over_tiles(&mut pixels, |start_index, tile| {
+            let mut stats = TraceStats::default();
+            for (offset, pixel) in tile.iter_mut().enumerate() {
+                let index = start_index + offset;
+                pcg::seed(options.seed, frame_index, index as u64);
+                let index = vec2!(index % size.x, index / size.x);
+                let position = Vec2f::from(index) / Vec2f::from(size);
+                let payload =
+                    scene.trace_screen(camera, position, aspect_ratio, options, &mut stats);
+                *pixel = payload.color;
+            }
+            *overall_stats.lock().unwrap() += stats;
+        });
+        let overall_stats = overall_stats.into_inner().unwrap();
+        (Self { size, pixels }, overall_stats)
+    }
+
+    pub(crate) fn to_rgb8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|color| color.to_rgb8())
+            .collect()
+    }
+
+    // The format is chosen by the file extension
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        let bytes = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ppm") => ppm::encode(self),
+            Some("png") => png::encode(self),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "unsupported image format, expected .ppm or .png",
+                ));
+            }
+        };
+        std::fs::write(path, bytes)
+    }
+}
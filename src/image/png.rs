@@ -0,0 +1,105 @@
+use crate::image::Image;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+// Stored deflate blocks are limited by their 16-bit length field
+const MAX_STORED_BLOCK_LEN: usize = 0xffff;
+
+// https://www.w3.org/TR/png-3/
+//
+// Image data is zlib-compressed with stored (uncompressed) deflate blocks only, which is valid for
+// every decoder and needs no compression code at all.
+pub(crate) fn encode(image: &Image) -> Vec<u8> {
+    let mut bytes = SIGNATURE.to_vec();
+
+    let mut header = vec![];
+    header.extend((image.size.x as u32).to_be_bytes());
+    header.extend((image.size.y as u32).to_be_bytes());
+    // 8-bit depth, truecolor, deflate, adaptive filtering, no interlace
+    header.extend([8, 2, 0, 0, 0]);
+    write_chunk(&mut bytes, b"IHDR", &header);
+
+    // Every scanline starts with its filter type, and 0 means no filtering
+    let rgb = image.to_rgb8();
+    let mut scanlines = Vec::with_capacity(rgb.len() + image.size.y);
+    for row in rgb.chunks(3 * image.size.x.max(1)) {
+        scanlines.push(0);
+        scanlines.extend(row);
+    }
+    write_chunk(&mut bytes, b"IDAT", &zlib_stored(&scanlines));
+
+    write_chunk(&mut bytes, b"IEND", &[]);
+    bytes
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    bytes.extend((data.len() as u32).to_be_bytes());
+    let crc_start = bytes.len();
+    bytes.extend(kind);
+    bytes.extend(data);
+    let crc = crc32(&bytes[crc_start..]);
+    bytes.extend(crc.to_be_bytes());
+}
+
+// https://www.rfc-editor.org/rfc/rfc1950 and https://www.rfc-editor.org/rfc/rfc1951#section-3.2.4
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate with 32K window and no preset dictionary, the check bits make it a multiple of 31
+    let mut bytes = vec![0x78, 0x01];
+    let mut blocks = data.chunks(MAX_STORED_BLOCK_LEN).peekable();
+    if blocks.peek().is_none() {
+        // An empty final block
+        bytes.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        bytes.push(is_final as u8);
+        let len = block.len() as u16;
+        bytes.extend(len.to_le_bytes());
+        bytes.extend((!len).to_le_bytes());
+        bytes.extend(block);
+    }
+    bytes.extend(adler32(data).to_be_bytes());
+    bytes
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    const POLYNOMIAL: u32 = 0xedb88320;
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MODULO: u32 = 65521;
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for &byte in data {
+        a = (a + byte as u32) % MODULO;
+        b = (b + a) % MODULO;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod test {
+    use super::{adler32, crc32, zlib_stored};
+
+    #[test]
+    fn test() {
+        assert_eq!(crc32(b"IEND"), 0xae426082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+        let data = vec![7; 70000];
+        let zlib = zlib_stored(&data);
+        // Header, two blocks with their headers, and the checksum
+        assert_eq!(zlib.len(), 2 + 2 * 5 + data.len() + 4);
+        assert_eq!((zlib[0] as u16 * 256 + zlib[1] as u16) % 31, 0);
+    }
+}
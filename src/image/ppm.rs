@@ -0,0 +1,8 @@
+use crate::image::Image;
+
+// Binary PPM (P6) as described in https://netpbm.sourceforge.net/doc/ppm.html
+pub(crate) fn encode(image: &Image) -> Vec<u8> {
+    let mut bytes = format!("P6\n{} {}\n255\n", image.size.x, image.size.y).into_bytes();
+    bytes.extend(image.to_rgb8());
+    bytes
+}
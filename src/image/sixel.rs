@@ -0,0 +1,108 @@
+use std::fmt::Write;
+
+use crate::{
+    color_mode::{ColorMode, TerminalColor, palette_256_rgb},
+    image::Image,
+    math::vec2::vec2,
+};
+
+// Every sixel character holds a column of six pixels
+const SIXEL_HEIGHT: usize = 6;
+const SIXEL_OFFSET: u8 = 0x3f;
+const PALETTE_SIZE: usize = 256;
+
+// https://vt100.net/docs/vt3xx-gp/chapter14.html
+//
+// Colors are quantized to the xterm 256-color palette with ordered dithering, so the palette
+// registers are the same every frame and no color search over the image is needed.
+pub(crate) fn encode(image: &Image) -> String {
+    let size = image.size;
+    let indices: Vec<u8> = image
+        .pixels
+        .iter()
+        .enumerate()
+        .map(|(index, &color)| {
+            let position = vec2!(index % size.x, index / size.x);
+            match ColorMode::Palette256.quantize(color, Some(position)) {
+                TerminalColor::Indexed256(index) => index,
+                _ => unreachable!(),
+            }
+        })
+        .collect();
+    let mut is_used = [false; PALETTE_SIZE];
+    for &index in &indices {
+        is_used[index as usize] = true;
+    }
+
+    // Pixels without a color are left as is, and raster attributes set the 1:1 aspect ratio
+    let mut sixel = format!("\x1bP0;1;0q\"1;1;{};{}", size.x, size.y);
+    for (index, _) in is_used.iter().enumerate().filter(|(_, is_used)| **is_used) {
+        // RGB components are in percents
+        let [r, g, b] = palette_256_rgb(index as u8).map(|value| value as u32 * 100 / 255);
+        let _ = write!(sixel, "#{index};2;{r};{g};{b}");
+    }
+    let mut bits = vec![0; size.x];
+    for band_start in (0..size.y).step_by(SIXEL_HEIGHT) {
+        let band_end = (band_start + SIXEL_HEIGHT).min(size.y);
+        let mut band_colors = [false; PALETTE_SIZE];
+        for &index in &indices[band_start * size.x..band_end * size.x] {
+            band_colors[index as usize] = true;
+        }
+        let mut is_first_color = true;
+        for color_index in (0..PALETTE_SIZE).filter(|&index| band_colors[index]) {
+            for (i, bits) in bits.iter_mut().enumerate() {
+                *bits = (band_start..band_end)
+                    .enumerate()
+                    .filter(|&(_, j)| indices[i + size.x * j] as usize == color_index)
+                    .fold(0, |bits, (row, _)| bits | 1 << row);
+            }
+            // Every next color goes over the same band again after a carriage return
+            if !is_first_color {
+                sixel.push('$');
+            }
+            is_first_color = false;
+            let _ = write!(sixel, "#{color_index}");
+            write_run_length_encoded(&mut sixel, &bits);
+        }
+        sixel.push('-');
+    }
+    sixel.push_str("\x1b\\");
+    sixel
+}
+
+// Repeated sixels are written as "!<count><sixel>" when it is shorter
+fn write_run_length_encoded(sixel: &mut String, bits: &[u8]) {
+    for run in bits.chunk_by(|a, b| a == b) {
+        let character = (SIXEL_OFFSET + run[0]) as char;
+        if run.len() > 3 {
+            let _ = write!(sixel, "!{}{character}", run.len());
+        } else {
+            for _ in run {
+                sixel.push(character);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{color::Color, image::Image, math::vec2::vec2};
+
+    use super::{encode, write_run_length_encoded};
+
+    #[test]
+    fn test() {
+        let mut sixel = String::new();
+        write_run_length_encoded(&mut sixel, &[63, 63, 63, 63, 63, 0, 0, 1]);
+        assert_eq!(sixel, "!5~??@");
+        let image = Image {
+            size: vec2!(3, 7),
+            pixels: vec![Color::from_hex(0x808080); 21],
+        };
+        let sixel = encode(&image);
+        assert!(sixel.starts_with("\x1bP0;1;0q\"1;1;3;7#"));
+        // Two bands, the second one is only a single row high
+        assert_eq!(sixel.matches('-').count(), 2);
+        assert!(sixel.ends_with("-\x1b\\"));
+    }
+}
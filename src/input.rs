@@ -1,65 +1,78 @@
 use std::{
-    io::{Read, stdin},
-    mem::MaybeUninit,
-    os::fd::AsRawFd,
     sync::mpsc::{Receiver, channel},
     thread::spawn,
 };
 
-use libc::{TCSANOW, cfmakeraw, tcgetattr, tcsetattr, termios};
+use libc::{POLLIN, STDIN_FILENO, poll, pollfd, read};
 
+use crate::input_event::{InputEvent, Parsed};
+
+// How long to wait for the rest of a sequence before taking Esc as a key on its own. Terminals
+// send whole sequences at once, so it only has to cover slow connections.
+const ESCAPE_TIMEOUT_MS: i32 = 50;
+
+// Expects the terminal to be in raw mode, see TerminalGuard
 pub(crate) struct Input {
-    stdin_fd: i32,
-    old_termios: termios,
-    receiver: Receiver<char>,
+    receiver: Receiver<InputEvent>,
 }
 
 impl Input {
     pub(crate) fn new() -> Self {
-        let mut stdin = stdin();
-        let stdin_fd = stdin.as_raw_fd();
-        let mut old_termios: termios = unsafe { MaybeUninit::zeroed().assume_init() };
-        unsafe { tcgetattr(stdin_fd, &mut old_termios as *mut _) };
-        let mut new_termios = old_termios;
-        unsafe { cfmakeraw(&mut new_termios as *mut _) };
-        unsafe { tcsetattr(stdin_fd, TCSANOW, &new_termios as *const _) };
         let (sender, receiver) = channel();
         spawn(move || {
+            let mut bytes = vec![];
             loop {
-                let mut buf = [0];
-                stdin.read_exact(&mut buf).unwrap();
-                let byte = buf[0];
-                // Read only the first 128 bits of ASCII table and skip the later Unicode as
-                // described in https://en.wikipedia.org/wiki/UTF-8#Description
-                if byte >> 7 == 0 {
-                    let bytes = [byte, 0, 0, 0];
-                    let char = char::from_u32(u32::from_le_bytes(bytes)).unwrap();
-                    sender.send(char).unwrap();
-                } else if byte >> 5 == 0b110 {
-                    stdin.read_exact(&mut [0]).unwrap();
-                } else if byte >> 4 == 0b1110 {
-                    stdin.read_exact(&mut [0; 2]).unwrap();
-                } else if byte >> 3 == 0b11110 {
-                    stdin.read_exact(&mut [0; 3]).unwrap();
+                // Nothing to wait for unless there is an unfinished sequence
+                let timeout = if bytes.is_empty() {
+                    -1
                 } else {
-                    panic!("unknown Unicode sequence")
+                    ESCAPE_TIMEOUT_MS
+                };
+                let mut stdin = pollfd {
+                    fd: STDIN_FILENO,
+                    events: POLLIN,
+                    revents: 0,
+                };
+                let ready_count = unsafe { poll(&mut stdin, 1, timeout) };
+                // Interrupted by a signal
+                if ready_count < 0 {
+                    continue;
+                }
+                let is_timed_out = ready_count == 0;
+                if !is_timed_out {
+                    let mut buf = [0; 64];
+                    let read_count = unsafe { read(STDIN_FILENO, buf.as_mut_ptr() as *mut _, 64) };
+                    match read_count {
+                        // End of input
+                        0 => break,
+                        // Interrupted by a signal
+                        ..0 => continue,
+                        _ => bytes.extend(&buf[..read_count as usize]),
+                    }
+                }
+                loop {
+                    let parsed = match InputEvent::parse(&bytes) {
+                        Parsed::Incomplete if is_timed_out => InputEvent::parse_timed_out(&bytes),
+                        parsed => parsed,
+                    };
+                    let len = match parsed {
+                        Parsed::Event(event, len) => {
+                            if sender.send(event).is_err() {
+                                return;
+                            }
+                            len
+                        }
+                        Parsed::Skipped(len) => len,
+                        Parsed::Incomplete => break,
+                    };
+                    bytes.drain(..len);
                 }
             }
         });
-        Self {
-            stdin_fd,
-            old_termios,
-            receiver,
-        }
+        Self { receiver }
     }
 
-    pub(crate) fn pop(&mut self) -> Option<char> {
+    pub(crate) fn pop(&mut self) -> Option<InputEvent> {
         self.receiver.try_recv().ok()
     }
 }
-
-impl Drop for Input {
-    fn drop(&mut self) {
-        unsafe { tcsetattr(self.stdin_fd, TCSANOW, &self.old_termios as *const _) };
-    }
-}
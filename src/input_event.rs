@@ -0,0 +1,390 @@
+const ESCAPE: u8 = 0x1b;
+const DELETE: u8 = 0x7f;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Key {
+    Char(char),
+    Escape,
+    Enter,
+    Tab,
+    Backspace,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    // From F1 to F12
+    Function(u8),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Modifiers {
+    pub(crate) shift: bool,
+    pub(crate) alt: bool,
+    pub(crate) control: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct KeyEvent {
+    pub(crate) key: Key,
+    pub(crate) modifiers: Modifiers,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum MouseAction {
+    Press(MouseButton),
+    Release(MouseButton),
+    // Motion while the button is held
+    Drag(MouseButton),
+    ScrollUp,
+    ScrollDown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct MouseEvent {
+    pub(crate) action: MouseAction,
+    // Zero-based cell the pointer is over
+    pub(crate) row: usize,
+    pub(crate) column: usize,
+    pub(crate) modifiers: Modifiers,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum InputEvent {
+    Key(KeyEvent),
+    Mouse(MouseEvent),
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Parsed {
+    // The event and how many bytes it took
+    Event(InputEvent, usize),
+    // Bytes that are valid but mean nothing to the program are skipped
+    Skipped(usize),
+    // The bytes are a prefix of a longer sequence
+    Incomplete,
+}
+
+impl Parsed {
+    fn key(key: Key, len: usize) -> Self {
+        Self::key_with_modifiers(key, Modifiers::default(), len)
+    }
+
+    fn key_with_modifiers(key: Key, modifiers: Modifiers, len: usize) -> Self {
+        Self::Event(InputEvent::Key(KeyEvent { key, modifiers }), len)
+    }
+}
+
+impl InputEvent {
+    // Decodes the first event of the terminal input. A lone Esc can't be told apart from the
+    // start of a sequence by the bytes alone, so it is up to the caller to decide after a timeout
+    // with parse_timed_out.
+    pub(crate) fn parse(bytes: &[u8]) -> Parsed {
+        let Some(&first) = bytes.first() else {
+            return Parsed::Incomplete;
+        };
+        match first {
+            ESCAPE => match bytes.get(1) {
+                None => Parsed::Incomplete,
+                Some(b'[') => parse_csi(bytes),
+                Some(b'O') => parse_ss3(bytes),
+                // Terminals send Alt with a key as Esc followed by that key
+                Some(_) => match Self::parse(&bytes[1..]) {
+                    Parsed::Event(InputEvent::Key(mut event), len) => {
+                        event.modifiers.alt = true;
+                        Parsed::Event(InputEvent::Key(event), len + 1)
+                    }
+                    Parsed::Event(event, len) => Parsed::Event(event, len + 1),
+                    Parsed::Skipped(len) => Parsed::Skipped(len + 1),
+                    Parsed::Incomplete => Parsed::Incomplete,
+                },
+            },
+            b'\r' | b'\n' => Parsed::key(Key::Enter, 1),
+            b'\t' => Parsed::key(Key::Tab, 1),
+            DELETE | 0x08 => Parsed::key(Key::Backspace, 1),
+            // Ctrl with a letter clears the upper bits of the letter
+            0x01..=0x1a => {
+                let modifiers = Modifiers {
+                    control: true,
+                    ..Default::default()
+                };
+                let char = (b'a' + first - 1) as char;
+                Parsed::key_with_modifiers(Key::Char(char), modifiers, 1)
+            }
+            _ => parse_utf8(bytes),
+        }
+    }
+
+    // What the bytes mean when nothing has followed them in time
+    pub(crate) fn parse_timed_out(bytes: &[u8]) -> Parsed {
+        match bytes {
+            [] => Parsed::Incomplete,
+            [ESCAPE] => Parsed::key(Key::Escape, 1),
+            // Alt with Esc
+            [ESCAPE, ESCAPE] => {
+                let modifiers = Modifiers {
+                    alt: true,
+                    ..Default::default()
+                };
+                Parsed::key_with_modifiers(Key::Escape, modifiers, 2)
+            }
+            // Broken sequences are dropped as a whole
+            _ => Parsed::Skipped(bytes.len()),
+        }
+    }
+}
+
+// https://en.wikipedia.org/wiki/UTF-8#Description
+fn parse_utf8(bytes: &[u8]) -> Parsed {
+    let len = match bytes[0] {
+        byte if byte >> 7 == 0 => 1,
+        byte if byte >> 5 == 0b110 => 2,
+        byte if byte >> 4 == 0b1110 => 3,
+        byte if byte >> 3 == 0b11110 => 4,
+        _ => return Parsed::Skipped(1),
+    };
+    let Some(bytes) = bytes.get(..len) else {
+        return Parsed::Incomplete;
+    };
+    match str::from_utf8(bytes)
+        .ok()
+        .and_then(|text| text.chars().next())
+    {
+        Some(char) => Parsed::key(Key::Char(char), len),
+        None => Parsed::Skipped(len),
+    }
+}
+
+// SS3 is Esc O followed by a single byte
+fn parse_ss3(bytes: &[u8]) -> Parsed {
+    let Some(&last) = bytes.get(2) else {
+        return Parsed::Incomplete;
+    };
+    match final_key(last) {
+        Some(key) => Parsed::key(key, 3),
+        None => Parsed::Skipped(3),
+    }
+}
+
+// https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h2-PC-Style-Function-Keys
+//
+// CSI is Esc [ followed by parameter bytes, intermediate bytes, and the final byte
+fn parse_csi(bytes: &[u8]) -> Parsed {
+    let Some(final_index) = bytes[2..]
+        .iter()
+        .position(|byte| (0x40..=0x7e).contains(byte))
+        .map(|index| index + 2)
+    else {
+        // Anything but parameter and intermediate bytes breaks the sequence
+        return if bytes[2..].iter().all(|byte| (0x20..=0x3f).contains(byte)) {
+            Parsed::Incomplete
+        } else {
+            Parsed::Skipped(bytes.len())
+        };
+    };
+    let len = final_index + 1;
+    let final_byte = bytes[final_index];
+    // Mouse reports are told apart by the private parameter prefix
+    if bytes[2] == b'<' {
+        let parameters = parse_parameters(&bytes[3..final_index]);
+        return match parse_sgr_mouse(&parameters, final_byte) {
+            Some(event) => Parsed::Event(InputEvent::Mouse(event), len),
+            None => Parsed::Skipped(len),
+        };
+    }
+    let parameters = parse_parameters(&bytes[2..final_index]);
+    let parameter = |index: usize| parameters.get(index).copied().flatten();
+    // The modifier parameter is 1 plus the bits of Shift, Alt, and Ctrl
+    let modifiers = parameter(1).map_or(Modifiers::default(), |value| {
+        let bits = value.saturating_sub(1);
+        Modifiers {
+            shift: bits & 1 != 0,
+            alt: bits & 2 != 0,
+            control: bits & 4 != 0,
+        }
+    });
+    let key = match final_byte {
+        b'~' => match parameter(0) {
+            Some(1 | 7) => Some(Key::Home),
+            Some(2) => Some(Key::Insert),
+            Some(3) => Some(Key::Delete),
+            Some(4 | 8) => Some(Key::End),
+            Some(5) => Some(Key::PageUp),
+            Some(6) => Some(Key::PageDown),
+            Some(number @ 11..=15) => Some(Key::Function((number - 10) as u8)),
+            Some(number @ 17..=21) => Some(Key::Function((number - 11) as u8)),
+            Some(number @ 23..=24) => Some(Key::Function((number - 12) as u8)),
+            _ => None,
+        },
+        // Shift with Tab
+        b'Z' => {
+            let modifiers = Modifiers {
+                shift: true,
+                ..Default::default()
+            };
+            return Parsed::key_with_modifiers(Key::Tab, modifiers, len);
+        }
+        _ => final_key(final_byte),
+    };
+    match key {
+        Some(key) => Parsed::key_with_modifiers(key, modifiers, len),
+        None => Parsed::Skipped(len),
+    }
+}
+
+// Parameters are separated by semicolons, and the ones left out are None
+fn parse_parameters(bytes: &[u8]) -> Vec<Option<u32>> {
+    str::from_utf8(bytes)
+        .unwrap_or_default()
+        .split(';')
+        .map(|parameter| parameter.parse().ok())
+        .collect()
+}
+
+// https://invisible-island.net/xterm/ctlseqs/ctlseqs.html#h2-Extended-coordinates
+//
+// SGR reports are CSI < button ; column ; row followed by M on press and m on release. The low
+// bits of the button code are the button, and the higher ones are modifiers, motion, and wheel.
+fn parse_sgr_mouse(parameters: &[Option<u32>], final_byte: u8) -> Option<MouseEvent> {
+    let &[Some(code), Some(column), Some(row)] = parameters else {
+        return None;
+    };
+    let button = match code & 0b11 {
+        0 => Some(MouseButton::Left),
+        1 => Some(MouseButton::Middle),
+        2 => Some(MouseButton::Right),
+        _ => None,
+    };
+    let action = if code & 64 != 0 {
+        match code & 0b11 {
+            0 => MouseAction::ScrollUp,
+            1 => MouseAction::ScrollDown,
+            // Horizontal scrolling
+            _ => return None,
+        }
+    } else if code & 32 != 0 {
+        MouseAction::Drag(button?)
+    } else if final_byte == b'M' {
+        MouseAction::Press(button?)
+    } else if final_byte == b'm' {
+        MouseAction::Release(button?)
+    } else {
+        return None;
+    };
+    Some(MouseEvent {
+        action,
+        row: row.saturating_sub(1) as usize,
+        column: column.saturating_sub(1) as usize,
+        modifiers: Modifiers {
+            shift: code & 4 != 0,
+            alt: code & 8 != 0,
+            control: code & 16 != 0,
+        },
+    })
+}
+
+// Keys that are the same for CSI and SS3 sequences
+fn final_key(byte: u8) -> Option<Key> {
+    match byte {
+        b'A' => Some(Key::Up),
+        b'B' => Some(Key::Down),
+        b'C' => Some(Key::Right),
+        b'D' => Some(Key::Left),
+        b'H' => Some(Key::Home),
+        b'F' => Some(Key::End),
+        b'P'..=b'S' => Some(Key::Function(byte - b'P' + 1)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InputEvent, Key, Modifiers, MouseAction, MouseButton, MouseEvent, Parsed};
+
+    fn event(key: Key, shift: bool, alt: bool, control: bool, len: usize) -> Parsed {
+        let modifiers = Modifiers {
+            shift,
+            alt,
+            control,
+        };
+        Parsed::key_with_modifiers(key, modifiers, len)
+    }
+
+    fn mouse_event(action: MouseAction, row: usize, column: usize, len: usize) -> Parsed {
+        let event = MouseEvent {
+            action,
+            row,
+            column,
+            modifiers: Modifiers::default(),
+        };
+        Parsed::Event(InputEvent::Mouse(event), len)
+    }
+
+    #[test]
+    fn test() {
+        let parse = InputEvent::parse;
+        assert_eq!(parse(b"q"), event(Key::Char('q'), false, false, false, 1));
+        assert_eq!(
+            parse("ж".as_bytes()),
+            event(Key::Char('ж'), false, false, false, 2)
+        );
+        assert_eq!(parse(&"ж".as_bytes()[..1]), Parsed::Incomplete);
+        assert_eq!(parse(b"\x1a"), event(Key::Char('z'), false, false, true, 1));
+        assert_eq!(parse(b"\x1b"), Parsed::Incomplete);
+        assert_eq!(parse(b"\x1b[A"), event(Key::Up, false, false, false, 3));
+        assert_eq!(parse(b"\x1bOD"), event(Key::Left, false, false, false, 3));
+        assert_eq!(
+            parse(b"\x1b[1;5C"),
+            event(Key::Right, false, false, true, 6)
+        );
+        assert_eq!(
+            parse(b"\x1b[5~q"),
+            event(Key::PageUp, false, false, false, 4)
+        );
+        assert_eq!(
+            parse(b"\x1b[24;2~"),
+            event(Key::Function(12), true, false, false, 7)
+        );
+        assert_eq!(parse(b"\x1b[1;"), Parsed::Incomplete);
+        assert_eq!(
+            parse(b"\x1bx"),
+            event(Key::Char('x'), false, true, false, 2)
+        );
+        assert_eq!(parse(b"\x1b[99~"), Parsed::Skipped(5));
+        assert_eq!(
+            parse(b"\x1b[<0;3;5M"),
+            mouse_event(MouseAction::Press(MouseButton::Left), 4, 2, 9)
+        );
+        assert_eq!(
+            parse(b"\x1b[<34;10;1M"),
+            mouse_event(MouseAction::Drag(MouseButton::Right), 0, 9, 11)
+        );
+        assert_eq!(
+            parse(b"\x1b[<2;10;1m"),
+            mouse_event(MouseAction::Release(MouseButton::Right), 0, 9, 10)
+        );
+        assert_eq!(
+            parse(b"\x1b[<65;1;1M"),
+            mouse_event(MouseAction::ScrollDown, 0, 0, 10)
+        );
+        assert_eq!(parse(b"\x1b[<0;3"), Parsed::Incomplete);
+        let parse_timed_out = InputEvent::parse_timed_out;
+        assert_eq!(
+            parse_timed_out(b"\x1b"),
+            event(Key::Escape, false, false, false, 1)
+        );
+        assert_eq!(parse_timed_out(b"\x1b[1;"), Parsed::Skipped(4));
+    }
+}
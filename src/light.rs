@@ -0,0 +1,105 @@
+use crate::{
+    color::Color,
+    math::vec3::Vec3f,
+    util::{random, smoothstep},
+};
+
+// Area lights are sampled at random points, and more samples give smoother shadow penumbras
+const AREA_LIGHT_SAMPLE_COUNT: usize = 4;
+
+pub(crate) enum LightShape {
+    Point {
+        position: Vec3f,
+    },
+    Spot {
+        position: Vec3f,
+        direction: Vec3f,
+        // Half of the cone angle in radians
+        cone_angle: f32,
+        // Angle in radians over which the light fades out towards the cone edge
+        falloff: f32,
+    },
+    Rectangle {
+        corner: Vec3f,
+        edge_u: Vec3f,
+        edge_v: Vec3f,
+    },
+    Sphere {
+        center: Vec3f,
+        radius: f32,
+    },
+}
+
+pub(crate) struct Light {
+    pub(crate) color: Color,
+    pub(crate) intensity: f32,
+    pub(crate) shape: LightShape,
+}
+
+pub(crate) struct LightSample {
+    pub(crate) direction: Vec3f,
+    pub(crate) distance: f32,
+    pub(crate) radiance: Vec3f,
+}
+
+impl Light {
+    pub(crate) fn sample_count(&self) -> usize {
+        match self.shape {
+            LightShape::Point { .. } | LightShape::Spot { .. } => 1,
+            LightShape::Rectangle { .. } | LightShape::Sphere { .. } => AREA_LIGHT_SAMPLE_COUNT,
+        }
+    }
+
+    // Samples the light as seen from the position. The radiance of area lights is already
+    // divided between their samples.
+    pub(crate) fn sample(&self, position: Vec3f) -> LightSample {
+        let (light_position, factor) = match self.shape {
+            LightShape::Point { position } => (position, 1.0),
+            LightShape::Spot {
+                position: light_position,
+                direction,
+                cone_angle,
+                falloff,
+            } => {
+                let cos = (position - light_position).normalize().dot(direction);
+                let outer = cone_angle.cos();
+                let inner = (cone_angle - falloff).max(0.0).cos();
+                // Without a falloff the cone edge is hard, and smoothstep would divide by zero
+                let factor = if inner <= outer {
+                    if cos >= outer { 1.0 } else { 0.0 }
+                } else {
+                    smoothstep(outer, inner, cos)
+                };
+                (light_position, factor)
+            }
+            LightShape::Rectangle {
+                corner,
+                edge_u,
+                edge_v,
+            } => {
+                let light_position = corner + edge_u * random() + edge_v * random();
+                // Rectangle emits to both of its sides
+                let normal = edge_u.cross(edge_v).normalize();
+                let cos = (position - light_position).normalize().dot(normal).abs();
+                (light_position, cos / AREA_LIGHT_SAMPLE_COUNT as f32)
+            }
+            LightShape::Sphere { center, radius } => {
+                // Only the hemisphere facing the position is visible from it
+                let mut offset = Vec3f::random_unit();
+                if offset.dot(position - center) < 0.0 {
+                    offset = -offset;
+                }
+                let light_position = center + offset * radius;
+                (light_position, 1.0 / AREA_LIGHT_SAMPLE_COUNT as f32)
+            }
+        };
+        let to_light = light_position - position;
+        let distance = to_light.length();
+        let attenuation = factor * self.intensity / (distance * distance);
+        LightSample {
+            direction: to_light / distance,
+            distance,
+            radiance: self.color.0 * attenuation,
+        }
+    }
+}
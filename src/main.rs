@@ -1,87 +1,172 @@
+use std::{
+    env::args_os,
+    io::{ErrorKind, Write, stdout},
+    process::exit,
+};
+
 use camera::Camera;
 use color::Color;
+use image::Image;
 use input::Input;
-use math::vec2::vec2;
-use math::vec3::{Vec3f, vec3};
+use input_event::{InputEvent, Key, KeyEvent};
+use light::{Light, LightShape};
+use material::Material;
+use math::vec3::vec3;
+use navigation::Navigation;
+use obj::parse_obj;
 use object::Object;
+use options::{Options, OptionsError};
+use projection::Projection;
 use scene::Scene;
+use scene_file::{SceneFile, load_scene_file};
 use screen::Screen;
+use terminal::TerminalGuard;
 use util::average_sum::AverageSum;
 use util::timer::Timer;
 use view_mode::ViewMode;
 
 use crate::geometry::aabb::Aabb;
 use crate::geometry::sphere::Sphere;
-use crate::geometry::triangle::Triangle;
-use crate::geometry::triangular::Triangular;
 
 mod camera;
 mod color;
+mod color_mode;
 mod consts;
 mod escape;
 mod geometry;
+mod graphics_mode;
+mod image;
 mod input;
+mod input_event;
+mod light;
+mod material;
 mod math;
+mod navigation;
+mod obj;
 mod object;
+mod options;
+mod output;
 mod palette;
+mod projection;
 mod scene;
+mod scene_file;
 mod screen;
 mod sky;
 mod symbol;
+mod symbol_mode;
+mod terminal;
+mod text_parser;
 mod trace_payload;
 mod trace_stats;
 mod util;
 mod view_mode;
 
 fn main() {
-    let scene = build_scene();
-    let mut screen = Screen::new();
+    let options = match Options::parse(args_os()) {
+        Ok(options) => options,
+        Err(OptionsError::Help) => {
+            // The help is often piped to a pager that may quit before reading all of it
+            if let Err(error) = writeln!(stdout(), "{}", OptionsError::Help)
+                && error.kind() != ErrorKind::BrokenPipe
+            {
+                eprintln!("{error}");
+                exit(1);
+            }
+            return;
+        }
+        Err(error) => {
+            eprintln!("{error}");
+            exit(2);
+        }
+    };
+    let SceneFile { scene, camera } = match options.scene_path {
+        Some(path) => match load_scene_file(&path) {
+            Ok(scene_file) => scene_file,
+            Err(error) => {
+                eprintln!("{error}");
+                exit(1);
+            }
+        },
+        None => SceneFile {
+            scene: build_scene(),
+            camera: Camera::default(),
+        },
+    };
+    if let Some(output_path) = &options.output_path {
+        let mut camera = camera;
+        if camera.has_autofocus {
+            camera.autofocus(&scene);
+        }
+        let image = Image::render(&scene, &camera, &options.render, options.output_size);
+        if let Err(error) = image.save(output_path) {
+            eprintln!("{}: {error}", output_path.display());
+            exit(1);
+        }
+        return;
+    }
+    // Declared before the screen to be dropped after the last frame is written
+    let _terminal_guard = TerminalGuard::new();
+    let mut screen = Screen::new(options.render.thread_count);
     let mut timer = Timer::new();
-    const AVG_WINDOW: usize = 100;
-    let mut avg_fps = AverageSum::new(AVG_WINDOW);
-    let mut avg_ms = AverageSum::new(AVG_WINDOW);
-    let mut view_mode = ViewMode::default();
-    const ACCELERATION: f32 = 10.0;
-    let mut velocity = vec2!(0.0);
-    let mut position = vec2!(0.0f32);
+    let mut avg_fps = AverageSum::new(options.fps_window);
+    let mut avg_ms = AverageSum::new(options.fps_window);
+    let mut render_options = options.render;
+    let mut navigation = Navigation::new(&camera);
     let mut input = Input::new();
     loop {
         let time_delta = timer.tick().as_secs_f32();
 
-        if let Some(char) = input.pop() {
-            match char {
-                '1' => view_mode = ViewMode::Color,
-                '2' => view_mode = ViewMode::Normal,
-                '3' => view_mode = ViewMode::Depth,
-                '4' => view_mode = ViewMode::Complexity,
-                '\u{1b}' | 'q' => {
-                    break;
-                }
-                'h' => velocity.x -= ACCELERATION * time_delta,
-                'j' => velocity.y -= ACCELERATION * time_delta,
-                'k' => velocity.y += ACCELERATION * time_delta,
-                'l' => velocity.x += ACCELERATION * time_delta,
-                _ => (),
+        while let Some(event) = input.pop() {
+            match event {
+                // Navigation keys come first, since flying takes some of the other ones
+                InputEvent::Key(event) if navigation.handle_key(event, time_delta) => (),
+                InputEvent::Key(KeyEvent { key, modifiers }) => match key {
+                    Key::Char('1') => render_options.view_mode = ViewMode::Color,
+                    Key::Char('2') => render_options.view_mode = ViewMode::Normal,
+                    Key::Char('3') => render_options.view_mode = ViewMode::Depth,
+                    Key::Char('4') => render_options.view_mode = ViewMode::Complexity,
+                    Key::Char('z') if modifiers.control => terminal::suspend(),
+                    Key::Char('f') => navigation.toggle_mode(),
+                    Key::Char('g') => {
+                        render_options.graphics_mode = render_options.graphics_mode.next()
+                    }
+                    // Raw mode turns off signals, so Ctrl-C arrives as a key
+                    Key::Char('c') if modifiers.control => return,
+                    Key::Escape | Key::Char('q') => {
+                        return;
+                    }
+                    _ => (),
+                },
+                InputEvent::Mouse(event) => navigation.handle_mouse(event),
             }
         }
-
-        let next_position = position + velocity * time_delta;
-        position.x = next_position.x;
-        if next_position.y.abs() < 2.0 {
-            position.y = next_position.y;
-        } else {
-            velocity.y = 0.0;
-        }
-        velocity -= velocity * time_delta;
-        const LOOK_AT: Vec3f = vec3!(0.0);
-        let offset = vec3!(2.0 * position.x.sin(), position.y, 2.0 * position.x.cos());
-        let camera = Camera {
-            look_from: LOOK_AT + offset,
-            look_at: LOOK_AT,
-        };
+        let camera = navigation.update(time_delta, &scene);
 
         screen.append_overlay_text_line("Terminal Ray Tracer".to_owned());
-        screen.append_overlay_text_line(format!("View mode: {view_mode} (use 1-4 keys to change)"));
+        screen.append_overlay_text_line(format!(
+            "View mode: {} (use 1-4 keys to change)",
+            render_options.view_mode
+        ));
+        screen.append_overlay_text_line(format!(
+            "Navigation: {} (use f to change)",
+            navigation.mode()
+        ));
+        screen.append_overlay_text_line(format!(
+            "Camera: {} {}, {:.0}° roll, {:.1} near clip (use o [ ] , . - = to change)",
+            camera.projection,
+            match camera.projection {
+                Projection::Perspective => format!("{:.0}° fov", camera.vertical_fov.to_degrees()),
+                Projection::Orthographic => format!("{:.2} high", camera.view_height),
+            },
+            camera.roll.to_degrees(),
+            camera.near_clip
+        ));
+        screen.append_overlay_text_line(format!(
+            "Lens: {:.2} aperture, {:.2} focus{} (use ( ) < > p to change)",
+            camera.aperture_radius,
+            camera.focus_distance,
+            if camera.has_autofocus { " auto" } else { "" }
+        ));
         let fps = 1.0 / time_delta;
         avg_fps.add(fps);
         let ms = 1e3 * time_delta;
@@ -89,61 +174,85 @@ fn main() {
         screen.append_overlay_text_line(format!(
             "{ms:.0} ~{avg_ms:.0} ms, {fps:.1} ~{avg_fps:.1} fps"
         ));
-        screen.render(&scene, &camera, &view_mode);
-        screen.draw();
+        screen.render(&scene, &camera, &render_options);
+        screen.draw(&render_options);
     }
 }
 
 fn build_scene() -> Scene {
-    let mut scene = Scene::new();
-    scene.spawn(Object {
-        color: Color::RED,
-        intersect: Box::new(Sphere {
-            center: vec3!(-1.0, -1.0, 0.0),
-            radius: 0.5,
-        }),
-    });
-    let triangular = load_suzanne_obj();
-    scene.spawn(Object {
-        color: Color::GREEN,
-        intersect: Box::new(triangular),
-    });
-    scene.spawn(Object {
-        color: Color::BLUE,
-        intersect: Box::new(Aabb::centered(vec3!(1.0, -1.0, 0.0), 1.0 / 3.0)),
-    });
-    scene
-}
-
-fn load_suzanne_obj() -> Triangular {
-    let text = include_str!("suzanne.obj");
-    let mut vertices = vec![];
-    let mut index_triplets = vec![];
-    for line in text.lines() {
-        let (operator, values) = line.split_once(char::is_whitespace).unwrap();
-        match operator {
-            "v" => {
-                let values: Vec<f32> = values.split_whitespace().flat_map(str::parse).collect();
-                let [x, y, z] = values.try_into().unwrap();
-                let vertex = vec3!(x, y, z);
-                vertices.push(vertex);
-            }
-            "f" => {
-                let values: Vec<usize> = values.split_whitespace().flat_map(str::parse).collect();
-                let triplet: [_; 3] = values.try_into().unwrap();
-                index_triplets.push(triplet);
-            }
-            _ => panic!("unsupported OBJ operator {operator}"),
-        }
-    }
-    let mut builder = Triangular::builder();
-    for triplet in index_triplets {
-        let [i, j, k] = triplet;
-        let a = vertices[i - 1];
-        let b = vertices[j - 1];
-        let c = vertices[k - 1];
-        let triangle = Triangle::new(a, b, c);
-        builder = builder.add_triangle(triangle);
-    }
-    builder.build()
+    let triangular = parse_obj(include_str!("suzanne.obj")).unwrap();
+    Scene::builder()
+        .spawn(Object {
+            material: Material::Lambertian { albedo: Color::RED },
+            intersect: Box::new(Sphere {
+                center: vec3!(-1.0, -1.0, 0.0),
+                radius: 0.5,
+            }),
+        })
+        .spawn(Object {
+            material: Material::Dielectric {
+                refraction_index: 1.5,
+            },
+            intersect: Box::new(Sphere {
+                center: vec3!(0.0, -1.0, 1.0),
+                radius: 0.3,
+            }),
+        })
+        .spawn(Object {
+            material: Material::Emissive {
+                color: Color::from_hex(0xffe08a),
+            },
+            intersect: Box::new(Sphere {
+                center: vec3!(0.0, 1.0, 0.0),
+                radius: 0.1,
+            }),
+        })
+        .spawn(Object {
+            material: Material::Lambertian {
+                albedo: Color::GREEN,
+            },
+            intersect: Box::new(triangular),
+        })
+        .spawn(Object {
+            material: Material::Metal {
+                albedo: Color::BLUE,
+                roughness: 0.1,
+            },
+            intersect: Box::new(Aabb::centered(vec3!(1.0, -1.0, 0.0), 1.0 / 3.0)),
+        })
+        .add_light(Light {
+            color: Color::from_hex(0xffd9a0),
+            intensity: 1.0,
+            shape: LightShape::Point {
+                position: vec3!(1.5, 0.5, 1.5),
+            },
+        })
+        .add_light(Light {
+            color: Color::from_hex(0xffffff),
+            intensity: 2.0,
+            shape: LightShape::Spot {
+                position: vec3!(-1.0, 1.0, 1.0),
+                direction: vec3!(0.0, -2.0, -1.0).normalize(),
+                cone_angle: 0.4,
+                falloff: 0.1,
+            },
+        })
+        .add_light(Light {
+            color: Color::from_hex(0xa0c0ff),
+            intensity: 1.0,
+            shape: LightShape::Rectangle {
+                corner: vec3!(-0.5, 2.0, -0.5),
+                edge_u: vec3!(1.0, 0.0, 0.0),
+                edge_v: vec3!(0.0, 0.0, 1.0),
+            },
+        })
+        .add_light(Light {
+            color: Color::from_hex(0xffa0a0),
+            intensity: 0.5,
+            shape: LightShape::Sphere {
+                center: vec3!(1.0, 1.0, -1.0),
+                radius: 0.2,
+            },
+        })
+        .build()
 }
@@ -0,0 +1,211 @@
+use crate::{
+    color::Color,
+    consts::EPSILON,
+    geometry::{intersection::Intersection, ray::Ray},
+    math::vec3::{Vec3f, vec3},
+    util::random,
+};
+
+// Diffuse surfaces get most of their light directly from the sun, so the bounced light is
+// only a fraction of it
+const INDIRECT_DIFFUSE_RATIO: f32 = 0.2;
+// Most rays a single hit scatters into
+pub(crate) const MAX_SCATTER_COUNT: usize = 8;
+
+#[derive(Clone, Copy)]
+pub(crate) enum Material {
+    Lambertian { albedo: Color },
+    Metal { albedo: Color, roughness: f32 },
+    Dielectric { refraction_index: f32 },
+    Emissive { color: Color },
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Scattered {
+    pub(crate) ray: Ray,
+    pub(crate) attenuation: Vec3f,
+}
+
+// Scattering happens on every hit, so rays are returned in place instead of allocating
+pub(crate) type ScatteredRays = [Option<Scattered>; MAX_SCATTER_COUNT];
+
+impl Material {
+    pub(crate) fn emitted(&self) -> Vec3f {
+        match self {
+            Material::Emissive { color } => color.0,
+            _ => vec3!(0.0),
+        }
+    }
+
+    // Only the diffuse surfaces are lit directly, other ones just redirect rays
+    pub(crate) fn diffuse_albedo(&self) -> Option<Color> {
+        match self {
+            Material::Lambertian { albedo } => Some(*albedo),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn scatter(
+        &self,
+        ray: Ray,
+        intersection: &Intersection,
+        count: usize,
+    ) -> ScatteredRays {
+        if count == 0 {
+            return [None; MAX_SCATTER_COUNT];
+        }
+        let normal = intersection.normal;
+        match *self {
+            Material::Lambertian { albedo } => {
+                let origin = intersection.hit_position(ray);
+                let attenuation = albedo.0 * (INDIRECT_DIFFUSE_RATIO / count as f32);
+                collect_rays((0..count).map(|_| Scattered {
+                    ray: Ray {
+                        origin,
+                        direction: generate_diffuse_ray(normal),
+                    },
+                    attenuation,
+                }))
+            }
+            Material::Metal { albedo, roughness } => {
+                let origin = intersection.hit_position(ray);
+                // A perfect mirror reflects to the only direction
+                let count = if roughness > 0.0 { count } else { 1 };
+                let attenuation = albedo.0 * (1.0 / count as f32);
+                collect_rays((0..count).map(|_| Scattered {
+                    ray: Ray {
+                        origin,
+                        direction: generate_glossy_ray(ray.direction, normal, roughness),
+                    },
+                    attenuation,
+                }))
+            }
+            Material::Dielectric { refraction_index } => {
+                let (normal, eta) = if intersection.is_front_face(ray) {
+                    (normal, 1.0 / refraction_index)
+                } else {
+                    (-normal, refraction_index)
+                };
+                let reflected_direction = ray.direction.reflect(normal);
+                let reflected = |attenuation| Scattered {
+                    ray: Ray {
+                        origin: intersection.biased_origin(ray, reflected_direction),
+                        direction: reflected_direction,
+                    },
+                    attenuation: vec3!(attenuation),
+                };
+                let Some(refracted_direction) = ray.direction.refract(normal, eta) else {
+                    // Total internal reflection
+                    return collect_rays([reflected(1.0)]);
+                };
+                let refracted = |attenuation| Scattered {
+                    ray: Ray {
+                        origin: intersection.biased_origin(ray, refracted_direction),
+                        direction: refracted_direction,
+                    },
+                    attenuation: vec3!(attenuation),
+                };
+                // Fresnel equations are symmetric, so the angle on the less dense side is used
+                // either way
+                let cos = if eta < 1.0 {
+                    -ray.direction.dot(normal)
+                } else {
+                    -refracted_direction.dot(normal)
+                };
+                let reflectance = schlick_reflectance(cos, refraction_index);
+                if count > 1 {
+                    collect_rays([reflected(reflectance), refracted(1.0 - reflectance)])
+                } else if random() < reflectance {
+                    collect_rays([reflected(1.0)])
+                } else {
+                    collect_rays([refracted(1.0)])
+                }
+            }
+            Material::Emissive { .. } => [None; MAX_SCATTER_COUNT],
+        }
+    }
+}
+
+// Rays past the maximum count are dropped
+fn collect_rays(rays: impl IntoIterator<Item = Scattered>) -> ScatteredRays {
+    let mut collected = [None; MAX_SCATTER_COUNT];
+    for (slot, ray) in collected.iter_mut().zip(rays) {
+        *slot = Some(ray);
+    }
+    collected
+}
+
+// https://en.wikipedia.org/wiki/Schlick%27s_approximation
+fn schlick_reflectance(cos: f32, refraction_index: f32) -> f32 {
+    let r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
+    let r0 = r0 * r0;
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
+
+// Cosine weighted direction around the normal
+fn generate_diffuse_ray(normal: Vec3f) -> Vec3f {
+    let direction = normal + Vec3f::random_unit();
+    if direction.length() < EPSILON {
+        normal
+    } else {
+        direction.normalize()
+    }
+}
+
+fn generate_glossy_ray(incident: Vec3f, normal: Vec3f, roughness: f32) -> Vec3f {
+    let bias_direction = loop {
+        let random_unit = Vec3f::random_unit();
+        if random_unit.dot(normal) > 0.0 {
+            break random_unit.normalize();
+        };
+    };
+    (incident.reflect(normal) + bias_direction * roughness).normalize()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        geometry::{intersection::Intersection, ray::Ray},
+        math::vec3::vec3,
+    };
+
+    use super::{Material, schlick_reflectance};
+
+    #[test]
+    fn test() {
+        // Glass reflects 4% of the light at normal incidence, and all of it at grazing angles
+        assert!((schlick_reflectance(1.0, 1.5) - 0.04).abs() < 1e-4);
+        assert!((schlick_reflectance(0.0, 1.5) - 1.0).abs() < 1e-4);
+        let normal = vec3!(0.0, 1.0, 0.0);
+        let straight = vec3!(0.0, -1.0, 0.0);
+        assert!(
+            straight
+                .refract(normal, 1.0 / 1.5)
+                .is_some_and(|refracted| (refracted - straight).length() < 1e-6)
+        );
+        // Past the critical angle of about 42 degrees light can't leave glass
+        let grazing = vec3!(1.0, -1.0, 0.0).normalize();
+        assert!(grazing.refract(normal, 1.5).is_none());
+        assert!(grazing.refract(normal, 1.0 / 1.5).is_some());
+        // From the inside the normal still looks outside, and only the reflection is scattered
+        let ray = Ray {
+            origin: vec3!(0.0),
+            direction: -grazing,
+        };
+        let intersection = Intersection {
+            distance: 1.0,
+            normal,
+        };
+        let material = Material::Dielectric {
+            refraction_index: 1.5,
+        };
+        let scattered: Vec<_> = material
+            .scatter(ray, &intersection, 2)
+            .into_iter()
+            .flatten()
+            .collect();
+        assert_eq!(scattered.len(), 1);
+        assert!(scattered[0].attenuation == vec3!(1.0));
+        assert!(scattered[0].ray.direction.dot(normal) < 0.0);
+    }
+}
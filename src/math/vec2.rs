@@ -1,6 +1,11 @@
-use std::ops::{Add, AddAssign, Div, Mul, Sub, SubAssign};
+use std::{
+    f32::consts::PI,
+    ops::{Add, AddAssign, Div, Mul, Sub, SubAssign},
+};
 
-#[derive(Clone, Copy)]
+use crate::util::random;
+
+#[derive(Clone, Copy, PartialEq)]
 pub(crate) struct Vec2<T> {
     pub(crate) x: T,
     pub(crate) y: T,
@@ -39,6 +44,15 @@ where
     }
 }
 
+impl Vec2f {
+    // Uniformly distributed over the area, so the radius is the square root of a uniform value
+    pub(crate) fn random_in_unit_disk() -> Self {
+        let radius = random().sqrt();
+        let (sin, cos) = (2.0 * PI * random()).sin_cos();
+        vec2!(radius * cos, radius * sin)
+    }
+}
+
 impl<T> Add for Vec2<T>
 where
     T: Add<Output = T>,
@@ -129,6 +143,17 @@ impl Mul<Vec2f> for f32 {
     }
 }
 
+impl<T> Sub for Vec2<T>
+where
+    T: Sub<Output = T>,
+{
+    type Output = Vec2<T>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        vec2!(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
 impl<T> Sub<T> for Vec2<T>
 where
     T: Sub<Output = T> + Copy,
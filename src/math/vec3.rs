@@ -1,13 +1,13 @@
 use std::{
     iter::Sum,
-    ops::{Add, Div, Mul, MulAssign, Neg, Sub},
+    ops::{Add, AddAssign, Div, Index, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
 use crate::util::random;
 
 use super::traits::{Fract, Max, Min, One, Signum, Sqrt, Zero};
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, PartialEq)]
 pub(crate) struct Vec3<T> {
     pub(crate) x: T,
     pub(crate) y: T,
@@ -145,6 +145,18 @@ impl Vec3f {
     pub(crate) fn reflect(self, normal: Self) -> Self {
         self - normal * 2.0 * normal.dot(self)
     }
+
+    // Both vectors are expected to be normalized and to look to the opposite sides. The eta is
+    // the ratio of the refraction indices, and nothing is returned on total internal reflection.
+    pub(crate) fn refract(self, normal: Self, eta: f32) -> Option<Self> {
+        let cos_incident = -self.dot(normal);
+        let sin_transmitted_sqr = eta * eta * (1.0 - cos_incident * cos_incident);
+        if sin_transmitted_sqr > 1.0 {
+            return None;
+        }
+        let cos_transmitted = (1.0 - sin_transmitted_sqr).sqrt();
+        Some(self * eta + normal * (eta * cos_incident - cos_transmitted))
+    }
 }
 
 impl<T> Add for Vec3<T>
@@ -169,6 +181,17 @@ where
     }
 }
 
+impl<T> AddAssign for Vec3<T>
+where
+    T: AddAssign,
+{
+    fn add_assign(&mut self, rhs: Self) {
+        self.x += rhs.x;
+        self.y += rhs.y;
+        self.z += rhs.z;
+    }
+}
+
 impl<T> Div<T> for Vec3<T>
 where
     T: Div<Output = T> + Copy,
@@ -191,6 +214,19 @@ where
     }
 }
 
+impl<T> Index<usize> for Vec3<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index {index} is out of bounds"),
+        }
+    }
+}
+
 impl<T> Mul for Vec3<T>
 where
     T: Mul<Output = T>,
@@ -257,6 +293,17 @@ where
     }
 }
 
+impl<T> SubAssign for Vec3<T>
+where
+    T: SubAssign,
+{
+    fn sub_assign(&mut self, rhs: Self) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+        self.z -= rhs.z;
+    }
+}
+
 impl<T> Sum for Vec3<T>
 where
     T: Zero + Add<Output = T>,
@@ -0,0 +1,372 @@
+use std::{
+    f32::consts::{FRAC_PI_2, PI},
+    fmt::{Display, Formatter, Result},
+};
+
+use crate::{
+    camera::{Camera, MAX_FOV, MIN_FOV},
+    input_event::{Key, KeyEvent, MouseAction, MouseButton, MouseEvent},
+    math::{
+        vec2::{Vec2f, vec2},
+        vec3::{Vec3f, vec3},
+    },
+    projection::Projection,
+    scene::Scene,
+};
+
+const ACCELERATION: f32 = 10.0;
+// Per cell dragged, in radians for turning and in orbit radii for height and panning
+const DRAG_TURN_SPEED: f32 = 0.05;
+const DRAG_PAN_SPEED: f32 = 0.02;
+// Per wheel step, in the natural logarithm of the orbit radius, or in the distance to the target
+// when flying
+const ZOOM_SPEED: f32 = 0.2;
+// Per key press of the camera settings
+const FOV_STEP: f32 = PI / 36.0;
+const VIEW_HEIGHT_STEP: f32 = 1.1;
+const ROLL_STEP: f32 = PI / 36.0;
+const NEAR_CLIP_STEP: f32 = 0.1;
+const APERTURE_STEP: f32 = 0.01;
+const FOCUS_STEP: f32 = 1.1;
+// Looking straight up or down leaves the camera without a right direction
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.05;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum NavigationMode {
+    // Around the target, keeping the horizontal distance to it
+    #[default]
+    Orbit,
+    // First-person, turning in place and moving along the camera directions
+    Fly,
+}
+
+impl NavigationMode {
+    fn name(&self) -> &'static str {
+        match self {
+            NavigationMode::Orbit => "orbit",
+            NavigationMode::Fly => "fly",
+        }
+    }
+}
+
+impl Display for NavigationMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str(self.name())
+    }
+}
+
+// Moves the camera with keys and the mouse. Both modes keep the camera position and direction
+// up to date, and every mode takes over from them, so switching does not move the view.
+pub(crate) struct Navigation {
+    mode: NavigationMode,
+    // Only the position and the target are moved, the rest is left as it is
+    camera: Camera,
+    // Orbit angle around the vertical axis and height over the target
+    orbit_position: Vec2f,
+    orbit_radius: f32,
+    orbit_velocity: Vec2f,
+    // Yaw and pitch of the direction the camera looks in
+    fly_angles: Vec2f,
+    fly_angular_velocity: Vec2f,
+    // Along the right, up, and forward directions of the camera
+    fly_velocity: Vec3f,
+    // Along the right and up directions of the camera, in both modes
+    pan_velocity: Vec2f,
+    zoom_velocity: f32,
+    // Button held and the cell it was over the last time
+    drag: Option<(MouseButton, Vec2f)>,
+}
+
+impl Navigation {
+    pub(crate) fn new(camera: &Camera) -> Self {
+        let mut navigation = Self {
+            mode: NavigationMode::Orbit,
+            camera: *camera,
+            orbit_position: vec2!(0.0),
+            orbit_radius: 0.0,
+            orbit_velocity: vec2!(0.0),
+            fly_angles: vec2!(0.0),
+            fly_angular_velocity: vec2!(0.0),
+            fly_velocity: vec3!(0.0),
+            pan_velocity: vec2!(0.0),
+            zoom_velocity: 0.0,
+            drag: None,
+        };
+        navigation.take_over_orbit();
+        navigation
+    }
+
+    pub(crate) fn mode(&self) -> NavigationMode {
+        self.mode
+    }
+
+    pub(crate) fn toggle_mode(&mut self) {
+        match self.mode {
+            NavigationMode::Orbit => self.take_over_fly(),
+            NavigationMode::Fly => self.take_over_orbit(),
+        }
+    }
+
+    // Orbits around the point the camera looks at
+    fn take_over_orbit(&mut self) {
+        let offset = self.camera.look_from - self.camera.look_at;
+        self.orbit_radius = offset.x.hypot(offset.z);
+        self.orbit_position = vec2!(offset.x.atan2(offset.z), offset.y);
+        self.orbit_velocity = vec2!(0.0);
+        self.mode = NavigationMode::Orbit;
+    }
+
+    // Flies from where the camera is, in the direction it looks
+    fn take_over_fly(&mut self) {
+        let forward = (self.camera.look_at - self.camera.look_from).normalize();
+        self.fly_angles = vec2!(
+            forward.x.atan2(forward.z),
+            forward.y.asin().clamp(-MAX_PITCH, MAX_PITCH)
+        );
+        self.fly_angular_velocity = vec2!(0.0);
+        self.fly_velocity = vec3!(0.0);
+        self.mode = NavigationMode::Fly;
+    }
+
+    // Tells whether the key is one of the navigation ones
+    pub(crate) fn handle_key(&mut self, event: KeyEvent, time_delta: f32) -> bool {
+        // Combinations with Ctrl or Alt are not navigation, e.g. Ctrl-Z and Ctrl-C
+        if event.modifiers.control || event.modifiers.alt {
+            return false;
+        }
+        self.handle_settings_key(event) || self.handle_motion_key(event, time_delta)
+    }
+
+    // Camera settings are the same in every mode
+    fn handle_settings_key(&mut self, event: KeyEvent) -> bool {
+        let camera = &mut self.camera;
+        match event.key {
+            Key::Char('o') => camera.projection = camera.projection.next(),
+            // Narrower and wider view
+            Key::Char(char @ ('[' | ']')) => {
+                let sign = if char == '[' { -1.0 } else { 1.0 };
+                match camera.projection {
+                    Projection::Perspective => {
+                        camera.vertical_fov =
+                            (camera.vertical_fov + sign * FOV_STEP).clamp(MIN_FOV, MAX_FOV)
+                    }
+                    Projection::Orthographic => camera.view_height *= VIEW_HEIGHT_STEP.powf(sign),
+                }
+            }
+            Key::Char(',') => camera.roll += ROLL_STEP,
+            Key::Char('.') => camera.roll -= ROLL_STEP,
+            Key::Char('-') => camera.near_clip = (camera.near_clip - NEAR_CLIP_STEP).max(0.0),
+            Key::Char('=' | '+') => camera.near_clip += NEAR_CLIP_STEP,
+            Key::Char('(') => {
+                camera.aperture_radius = (camera.aperture_radius - APERTURE_STEP).max(0.0)
+            }
+            Key::Char(')') => camera.aperture_radius += APERTURE_STEP,
+            // Focusing by hand takes over from the autofocus
+            Key::Char(char @ ('<' | '>')) => {
+                let sign = if char == '<' { -1.0 } else { 1.0 };
+                camera.focus_distance *= FOCUS_STEP.powf(sign);
+                camera.has_autofocus = false;
+            }
+            Key::Char('p') => camera.has_autofocus = !camera.has_autofocus,
+            _ => return false,
+        }
+        true
+    }
+
+    fn handle_motion_key(&mut self, event: KeyEvent, time_delta: f32) -> bool {
+        let impulse = ACCELERATION * time_delta;
+        match self.mode {
+            NavigationMode::Orbit => match event.key {
+                Key::Char('h') | Key::Left => self.orbit_velocity.x -= impulse,
+                Key::Char('j') | Key::Down => self.orbit_velocity.y -= impulse,
+                Key::Char('k') | Key::Up => self.orbit_velocity.y += impulse,
+                Key::Char('l') | Key::Right => self.orbit_velocity.x += impulse,
+                _ => return false,
+            },
+            NavigationMode::Fly => match event.key {
+                Key::Char('a') => self.fly_velocity.x -= impulse,
+                Key::Char('d') => self.fly_velocity.x += impulse,
+                Key::Char('q') => self.fly_velocity.y -= impulse,
+                Key::Char('e') => self.fly_velocity.y += impulse,
+                Key::Char('s') => self.fly_velocity.z -= impulse,
+                Key::Char('w') => self.fly_velocity.z += impulse,
+                // Turning right decreases the yaw
+                Key::Char('h') | Key::Left => self.fly_angular_velocity.x += impulse,
+                Key::Char('j') | Key::Down => self.fly_angular_velocity.y -= impulse,
+                Key::Char('k') | Key::Up => self.fly_angular_velocity.y += impulse,
+                Key::Char('l') | Key::Right => self.fly_angular_velocity.x -= impulse,
+                _ => return false,
+            },
+        }
+        true
+    }
+
+    pub(crate) fn handle_mouse(&mut self, event: MouseEvent) {
+        let cell = vec2!(event.column as f32, event.row as f32);
+        let distance = (self.camera.look_at - self.camera.look_from).length();
+        match event.action {
+            MouseAction::Press(button) => self.drag = Some((button, cell)),
+            MouseAction::Drag(button) => {
+                // Drags are reported even when the press was outside the window
+                if let Some((previous_button, previous_cell)) = self.drag
+                    && previous_button == button
+                {
+                    let delta = cell - previous_cell;
+                    match (button, self.mode) {
+                        // The scene follows the pointer
+                        (MouseButton::Left, NavigationMode::Orbit) => {
+                            self.orbit_velocity.x -= DRAG_TURN_SPEED * delta.x;
+                            self.orbit_velocity.y += DRAG_TURN_SPEED * self.orbit_radius * delta.y;
+                        }
+                        // The view follows the pointer
+                        (MouseButton::Left, NavigationMode::Fly) => {
+                            self.fly_angular_velocity.x -= DRAG_TURN_SPEED * delta.x;
+                            self.fly_angular_velocity.y -= DRAG_TURN_SPEED * delta.y;
+                        }
+                        (MouseButton::Right, _) => {
+                            self.pan_velocity.x -= DRAG_PAN_SPEED * distance * delta.x;
+                            self.pan_velocity.y += DRAG_PAN_SPEED * distance * delta.y;
+                        }
+                        (MouseButton::Middle, _) => (),
+                    }
+                }
+                self.drag = Some((button, cell));
+            }
+            MouseAction::Release(_) => self.drag = None,
+            MouseAction::ScrollUp | MouseAction::ScrollDown => {
+                let direction = if event.action == MouseAction::ScrollUp {
+                    1.0
+                } else {
+                    -1.0
+                };
+                match self.mode {
+                    NavigationMode::Orbit => self.zoom_velocity -= ZOOM_SPEED * direction,
+                    NavigationMode::Fly => self.fly_velocity.z += ZOOM_SPEED * distance * direction,
+                }
+            }
+        }
+    }
+
+    // Moves the camera by the velocities, which slow down over time, and focuses it on the scene
+    // when the autofocus is on
+    pub(crate) fn update(&mut self, time_delta: f32, scene: &Scene) -> Camera {
+        match self.mode {
+            NavigationMode::Orbit => {
+                let next_position = self.orbit_position + self.orbit_velocity * time_delta;
+                self.orbit_position.x = next_position.x;
+                // Flying may leave the camera above the limit, from where it can only come down
+                let max_height = self.orbit_radius.max(self.orbit_position.y.abs());
+                if next_position.y.abs() < max_height {
+                    self.orbit_position.y = next_position.y;
+                } else {
+                    self.orbit_velocity.y = 0.0;
+                }
+                // Height is scaled along with the radius to keep the direction to the target
+                let zoom = (self.zoom_velocity * time_delta).exp();
+                self.orbit_radius *= zoom;
+                self.orbit_position.y *= zoom;
+                // Moving closer changes nothing in the orthographic projection otherwise
+                if self.camera.projection == Projection::Orthographic {
+                    self.camera.view_height *= zoom;
+                }
+                let offset = vec3!(
+                    self.orbit_radius * self.orbit_position.x.sin(),
+                    self.orbit_position.y,
+                    self.orbit_radius * self.orbit_position.x.cos()
+                );
+                self.camera.look_from = self.camera.look_at + offset;
+            }
+            NavigationMode::Fly => {
+                self.fly_angles += self.fly_angular_velocity * time_delta;
+                self.fly_angles.y = self.fly_angles.y.clamp(-MAX_PITCH, MAX_PITCH);
+                let (yaw, pitch) = (self.fly_angles.x, self.fly_angles.y);
+                let forward = vec3!(
+                    yaw.sin() * pitch.cos(),
+                    pitch.sin(),
+                    yaw.cos() * pitch.cos()
+                );
+                // The target stays at the same distance ahead to be orbited around later
+                let distance = (self.camera.look_at - self.camera.look_from).length();
+                let (right, up, forward) = Camera {
+                    look_at: self.camera.look_from + forward,
+                    ..self.camera
+                }
+                .basis();
+                self.camera.look_from += (right * self.fly_velocity.x
+                    + up * self.fly_velocity.y
+                    + forward * self.fly_velocity.z)
+                    * time_delta;
+                self.camera.look_at = self.camera.look_from + forward * distance;
+            }
+        }
+        let (right, up, _) = self.camera.basis();
+        let pan = (right * self.pan_velocity.x + up * self.pan_velocity.y) * time_delta;
+        self.camera.look_from += pan;
+        self.camera.look_at += pan;
+        self.orbit_velocity -= self.orbit_velocity * time_delta;
+        self.fly_angular_velocity -= self.fly_angular_velocity * time_delta;
+        self.fly_velocity -= self.fly_velocity * time_delta;
+        self.pan_velocity -= self.pan_velocity * time_delta;
+        self.zoom_velocity -= self.zoom_velocity * time_delta;
+        if self.camera.has_autofocus {
+            self.camera.autofocus(scene);
+        }
+        self.camera
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Navigation, NavigationMode};
+    use crate::{
+        camera::Camera,
+        input_event::{Key, KeyEvent, Modifiers},
+        math::vec3::vec3,
+        scene::Scene,
+    };
+
+    fn press(navigation: &mut Navigation, char: char) {
+        let event = KeyEvent {
+            key: Key::Char(char),
+            modifiers: Modifiers::default(),
+        };
+        assert!(navigation.handle_key(event, 0.1));
+    }
+
+    #[test]
+    fn test() {
+        let scene = Scene::builder().build();
+        let mut navigation = Navigation::new(&Camera {
+            look_from: vec3!(1.0, 2.0, 3.0),
+            look_at: vec3!(0.0, 0.5, 0.0),
+            roll: 0.3,
+            ..Default::default()
+        });
+        press(&mut navigation, 'l');
+        press(&mut navigation, 'k');
+        let mut camera = navigation.update(0.1, &scene);
+        for char in ['f', 'w', 'd', 'e', 'k', 'h', 'f'] {
+            if char == 'f' {
+                navigation.toggle_mode();
+                // Switching takes over the view as it is
+                let next_camera = navigation.update(0.0, &scene);
+                assert!((next_camera.look_from - camera.look_from).length() < 1e-4);
+                let (_, _, forward) = camera.basis();
+                let (_, _, next_forward) = next_camera.basis();
+                assert!(next_forward.dot(forward) > 1.0 - 1e-4);
+            } else {
+                press(&mut navigation, char);
+            }
+            camera = navigation.update(0.1, &scene);
+        }
+        assert!(navigation.mode() == NavigationMode::Orbit);
+        let event = KeyEvent {
+            key: Key::Char('w'),
+            modifiers: Modifiers {
+                control: true,
+                ..Default::default()
+            },
+        };
+        assert!(!navigation.handle_key(event, 0.1));
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{
+    geometry::{triangle::Triangle, triangular::Triangular},
+    text_parser::{LineTokens, ParseError},
+};
+
+// Parses the Wavefront OBJ subset that is enough for geometry: vertices and polygonal faces.
+// Faces with more than three vertices are triangulated as a fan.
+pub(crate) fn parse_obj(text: &str) -> Result<Triangular, ParseError> {
+    let mut vertices = vec![];
+    let mut builder = Triangular::builder();
+    for (line_index, line) in text.lines().enumerate() {
+        let mut tokens = LineTokens::new(line_index, line);
+        if tokens.is_empty() {
+            continue;
+        }
+        let (column, operator) = tokens.next("operator")?;
+        match operator {
+            "v" => {
+                vertices.push(tokens.next_vec3("vertex coordinate")?);
+                // Optional w coordinate
+                if !tokens.is_finished() {
+                    tokens.next_f32("vertex coordinate")?;
+                }
+            }
+            "f" => {
+                let mut face = vec![];
+                while !tokens.is_finished() {
+                    let (column, token) = tokens.next("vertex index")?;
+                    // Texture and normal indices in "v/vt/vn" form are not used
+                    let index = token.split('/').next().unwrap_or_default();
+                    let vertex = index
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|index| vertices.get(index.checked_sub(1)?))
+                        .ok_or_else(|| {
+                            tokens.error(column, format!("invalid vertex index \"{token}\""))
+                        })?;
+                    face.push(*vertex);
+                }
+                if face.len() < 3 {
+                    return Err(tokens.error(column, "face needs at least 3 vertices".to_owned()));
+                }
+                for i in 1..face.len() - 1 {
+                    let triangle = Triangle::new(face[0], face[i], face[i + 1]);
+                    builder = builder.add_triangle(triangle);
+                }
+            }
+            // Attributes that do not affect geometry
+            "vn" | "vt" | "o" | "g" | "s" | "mtllib" | "usemtl" => continue,
+            _ if operator.starts_with('#') => continue,
+            _ => {
+                return Err(tokens.error(column, format!("unsupported operator \"{operator}\"")));
+            }
+        }
+        tokens.finish()?;
+    }
+    Ok(builder.build())
+}
@@ -1,10 +1,10 @@
 use crate::{
-    color::Color, geometry::intersect::Intersect, geometry::intersection::Intersection,
-    geometry::ray::Ray,
+    geometry::aabb::Aabb, geometry::intersect::Intersect, geometry::intersection::Intersection,
+    geometry::ray::Ray, material::Material,
 };
 
 pub(crate) struct Object {
-    pub(crate) color: Color,
+    pub(crate) material: Material,
     pub(crate) intersect: Box<dyn Intersect + Send + Sync>,
 }
 
@@ -12,4 +12,8 @@ impl Intersect for Object {
     fn intersect(&self, ray: Ray) -> Option<Intersection> {
         self.intersect.intersect(ray)
     }
+
+    fn aabb(&self) -> Aabb {
+        self.intersect.aabb()
+    }
 }
@@ -0,0 +1,285 @@
+use std::{
+    ffi::OsString,
+    fmt::{self, Display, Formatter},
+    num::NonZero,
+    path::PathBuf,
+    thread::available_parallelism,
+};
+
+use crate::{
+    color_mode::ColorMode,
+    graphics_mode::GraphicsMode,
+    material::MAX_SCATTER_COUNT,
+    math::vec2::{Vec2u, vec2},
+    symbol_mode::SymbolMode,
+    view_mode::ViewMode,
+};
+
+const USAGE: &str = "\
+Usage: terminal-ray-tracer [OPTIONS] [SCENE]
+
+Arguments:
+  [SCENE]  Scene file to render instead of the built-in scene
+
+Options:
+  -m, --view-mode <MODE>      Initial view mode: color, normal, depth, or complexity
+                              [default: color]
+  -b, --symbols <MODE>        How symbols are drawn: full, half-block for two samples per
+                              symbol, or ascii for characters without colors [default: full]
+  -g, --graphics <MODE>       How frames are drawn: cells, or sixel or kitty for pixel resolution
+                              when the terminal supports it [default: cells]
+  -C, --colors <MODE>         Terminal colors: truecolor, 256, or 16 [default: 256 or 16
+                              when TERM advertises them, otherwise truecolor]
+  -d, --reflection-depth <N>  How many times a ray may bounce, 0 to 8 [default: 2]
+  -c, --reflection-count <N>  Rays scattered on every bounce, 1 to 8 [default: 2]
+      --lens-samples <N>      Rays traced per pixel across the camera aperture, 1 to 64
+                              [default: 4]
+  -t, --threads <N>           Render thread count [default: available parallelism]
+      --seed <N>              Random seed, the same seed gives the same frames [default: 0]
+  -w, --fps-window <N>        Frame count to average FPS over [default: 100]
+  -o, --output <FILE>         Render a single frame to a .ppm or .png file without a terminal
+  -s, --size <WIDTHxHEIGHT>   Pixel size of the output image [default: 800x600]
+  -h, --help                  Print help";
+
+const MAX_REFLECTION_DEPTH: usize = 8;
+const MAX_REFLECTION_COUNT: usize = MAX_SCATTER_COUNT;
+const MAX_LENS_SAMPLE_COUNT: usize = 64;
+
+// Everything that controls how a frame is rendered and may be changed at runtime
+pub(crate) struct RenderOptions {
+    pub(crate) view_mode: ViewMode,
+    pub(crate) symbol_mode: SymbolMode,
+    pub(crate) graphics_mode: GraphicsMode,
+    pub(crate) color_mode: ColorMode,
+    pub(crate) reflection_depth: usize,
+    pub(crate) reflection_count: usize,
+    // Only used when the camera has an aperture, otherwise all rays would be the same
+    pub(crate) lens_sample_count: usize,
+    pub(crate) thread_count: usize,
+    // Mixed with the frame and pixel indices to seed the random generator of every pixel
+    pub(crate) seed: u64,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            view_mode: ViewMode::default(),
+            symbol_mode: SymbolMode::default(),
+            graphics_mode: GraphicsMode::default(),
+            color_mode: ColorMode::detect(),
+            reflection_depth: 2,
+            reflection_count: 2,
+            lens_sample_count: 4,
+            thread_count: available_parallelism().map(NonZero::get).unwrap_or(1),
+            seed: 0,
+        }
+    }
+}
+
+pub(crate) struct Options {
+    pub(crate) scene_path: Option<PathBuf>,
+    pub(crate) render: RenderOptions,
+    pub(crate) fps_window: usize,
+    pub(crate) output_path: Option<PathBuf>,
+    pub(crate) output_size: Vec2u,
+}
+
+pub(crate) enum OptionsError {
+    Help,
+    Invalid(String),
+}
+
+impl Display for OptionsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionsError::Help => f.write_str(USAGE),
+            OptionsError::Invalid(message) => f.write_fmt(format_args!(
+                "error: {message}\n\nFor more information, try '--help'."
+            )),
+        }
+    }
+}
+
+impl Options {
+    pub(crate) fn parse<I>(args: I) -> Result<Self, OptionsError>
+    where
+        I: IntoIterator<Item = OsString>,
+    {
+        let mut args = args.into_iter();
+        let mut options = Self {
+            scene_path: None,
+            render: RenderOptions::default(),
+            fps_window: 100,
+            output_path: None,
+            output_size: vec2!(800, 600),
+        };
+        // Skip the program name
+        args.next();
+        while let Some(arg) = args.next() {
+            // The scene path is kept as is, since paths don't have to be valid UTF-8
+            if !arg.as_encoded_bytes().starts_with(b"-") {
+                if options.scene_path.is_some() {
+                    return Err(OptionsError::Invalid(format!(
+                        "unexpected argument '{}'",
+                        arg.display()
+                    )));
+                }
+                options.scene_path = Some(PathBuf::from(arg));
+                continue;
+            }
+            let arg = into_string(arg)?;
+            // Both "--name value" and "--name=value" forms are accepted
+            let (name, inline_value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_owned(), Some(value.to_owned())),
+                None => (arg, None),
+            };
+            let mut value = || match inline_value.clone() {
+                Some(value) => Ok(value),
+                None => args
+                    .next()
+                    .ok_or_else(|| OptionsError::Invalid(format!("'{name}' requires a value")))
+                    .and_then(into_string),
+            };
+            match name.as_str() {
+                "-h" | "--help" => return Err(OptionsError::Help),
+                "-m" | "--view-mode" => {
+                    let value = value()?;
+                    options.render.view_mode = ViewMode::from_name(&value).ok_or_else(|| {
+                        OptionsError::Invalid(format!("invalid view mode '{value}'"))
+                    })?;
+                }
+                "-b" | "--symbols" => {
+                    let value = value()?;
+                    options.render.symbol_mode =
+                        SymbolMode::from_name(&value).ok_or_else(|| {
+                            OptionsError::Invalid(format!("invalid symbol mode '{value}'"))
+                        })?;
+                }
+                "-g" | "--graphics" => {
+                    let value = value()?;
+                    options.render.graphics_mode =
+                        GraphicsMode::from_name(&value).ok_or_else(|| {
+                            OptionsError::Invalid(format!("invalid graphics mode '{value}'"))
+                        })?;
+                }
+                "-C" | "--colors" => {
+                    let value = value()?;
+                    options.render.color_mode = ColorMode::from_name(&value).ok_or_else(|| {
+                        OptionsError::Invalid(format!("invalid color mode '{value}'"))
+                    })?;
+                }
+                "-d" | "--reflection-depth" => {
+                    options.render.reflection_depth =
+                        parse_in_range(&name, &value()?, 0, MAX_REFLECTION_DEPTH)?;
+                }
+                "-c" | "--reflection-count" => {
+                    options.render.reflection_count =
+                        parse_in_range(&name, &value()?, 1, MAX_REFLECTION_COUNT)?;
+                }
+                "--lens-samples" => {
+                    options.render.lens_sample_count =
+                        parse_in_range(&name, &value()?, 1, MAX_LENS_SAMPLE_COUNT)?;
+                }
+                "-t" | "--threads" => {
+                    options.render.thread_count = parse_in_range(&name, &value()?, 1, 1024)?;
+                }
+                "--seed" => {
+                    let value = value()?;
+                    options.render.seed = value.parse().map_err(|_| {
+                        OptionsError::Invalid(format!(
+                            "invalid value '{value}' for '{name}': expected a non-negative number"
+                        ))
+                    })?;
+                }
+                "-w" | "--fps-window" => {
+                    options.fps_window = parse_in_range(&name, &value()?, 1, 100_000)?;
+                }
+                "-o" | "--output" => {
+                    let value = value()?;
+                    if !value.ends_with(".ppm") && !value.ends_with(".png") {
+                        return Err(OptionsError::Invalid(format!(
+                            "invalid output '{value}': expected a .ppm or .png file"
+                        )));
+                    }
+                    options.output_path = Some(PathBuf::from(value));
+                }
+                "-s" | "--size" => {
+                    let value = value()?;
+                    let Some((width, height)) = value.split_once('x') else {
+                        return Err(OptionsError::Invalid(format!(
+                            "invalid size '{value}': expected WIDTHxHEIGHT"
+                        )));
+                    };
+                    const MAX_SIZE: usize = 16384;
+                    options.output_size = vec2!(
+                        parse_in_range(&name, width, 1, MAX_SIZE)?,
+                        parse_in_range(&name, height, 1, MAX_SIZE)?
+                    );
+                }
+                _ => return Err(OptionsError::Invalid(format!("unexpected option '{name}'"))),
+            }
+        }
+        Ok(options)
+    }
+}
+
+// Options and their values are matched as text
+fn into_string(arg: OsString) -> Result<String, OptionsError> {
+    arg.into_string().map_err(|arg| {
+        OptionsError::Invalid(format!(
+            "invalid argument '{}': expected valid UTF-8",
+            arg.display()
+        ))
+    })
+}
+
+fn parse_in_range(name: &str, value: &str, min: usize, max: usize) -> Result<usize, OptionsError> {
+    value
+        .parse()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+        .ok_or_else(|| {
+            OptionsError::Invalid(format!(
+                "invalid value '{value}' for '{name}': expected a number from {min} to {max}"
+            ))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::OsString;
+
+    use super::{Options, OptionsError};
+
+    fn parse(args: &[&str]) -> Result<Options, OptionsError> {
+        Options::parse(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn test() {
+        let Ok(options) = parse(&["trt", "-d", "3", "--reflection-count=4", "-s", "64x48", "a"])
+        else {
+            panic!("valid options are expected to parse");
+        };
+        assert_eq!(options.render.reflection_depth, 3);
+        assert_eq!(options.render.reflection_count, 4);
+        assert!(options.output_size.x == 64 && options.output_size.y == 48);
+        assert!(
+            options
+                .scene_path
+                .is_some_and(|path| path.as_os_str() == "a")
+        );
+        assert!(matches!(parse(&["trt", "--help"]), Err(OptionsError::Help)));
+        for args in [
+            &["trt", "--unknown"][..],
+            &["trt", "-d", "9"],
+            &["trt", "-c", "0"],
+            &["trt", "-s", "64x0"],
+            &["trt", "-t"],
+            &["trt", "a", "b"],
+            &["trt", "-o", "a.jpg"],
+        ] {
+            assert!(matches!(parse(args), Err(OptionsError::Invalid(_))));
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    io::{Write, stdout},
+    mem::take,
+    sync::mpsc::{Receiver, SyncSender, sync_channel},
+    thread::{JoinHandle, spawn},
+};
+
+use crate::{
+    color_mode::ColorMode,
+    escape::Escape,
+    graphics_mode::GraphicsMode,
+    image::{Image, kitty, sixel},
+    math::vec2::{Vec2u, vec2},
+    symbol::Symbol,
+    terminal,
+};
+
+// Every frame replaces the same image, so frames do not pile up in the terminal memory
+const KITTY_IMAGE_ID: u32 = 1;
+
+// Everything needed to encode a frame without looking back at the screen
+pub(crate) struct Frame {
+    pub(crate) symbols: Vec<Symbol>,
+    pub(crate) size_in_symbols: Vec2u,
+    // Frame rendered at pixel resolution when graphics are used instead of cells
+    pub(crate) image: Option<Image>,
+    pub(crate) graphics_mode: GraphicsMode,
+    pub(crate) color_mode: ColorMode,
+    pub(crate) has_colors: bool,
+    // The terminal content can't be trusted, so the screen is cleared and drawn from scratch
+    pub(crate) is_clear_needed: bool,
+}
+
+// Encodes and writes frames on its own thread, so the terminal consumes a frame while the next
+// one is traced. Symbol grids are double-buffered: one is rendered into while the other one is
+// being written.
+pub(crate) struct Output {
+    frame_sender: Option<SyncSender<Frame>>,
+    // Symbols of the written frames come back with the emitted byte count
+    written_receiver: Receiver<(Vec<Symbol>, usize)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Output {
+    pub(crate) fn new(is_synchronized_update_supported: bool) -> Self {
+        let (frame_sender, frame_receiver) = sync_channel(1);
+        let (written_sender, written_receiver) = sync_channel(1);
+        // The second grid is free from the start
+        written_sender.send((vec![], 0)).unwrap();
+        let thread = spawn(move || {
+            let mut writer = Writer {
+                is_synchronized_update_supported,
+                ..Default::default()
+            };
+            for frame in frame_receiver {
+                let byte_count = writer.write(&frame);
+                if written_sender.send((frame.symbols, byte_count)).is_err() {
+                    break;
+                }
+            }
+            writer.finish();
+        });
+        Self {
+            frame_sender: Some(frame_sender),
+            written_receiver,
+            thread: Some(thread),
+        }
+    }
+
+    // Waits for the previous frame to be written, then hands this one over. Returns the symbols
+    // of the previous frame to render the next one into, and how many bytes it took.
+    pub(crate) fn write(&mut self, frame: Frame) -> (Vec<Symbol>, usize) {
+        let written = self.written_receiver.recv().unwrap();
+        if let Some(frame_sender) = &self.frame_sender {
+            frame_sender.send(frame).unwrap();
+        }
+        written
+    }
+}
+
+impl Drop for Output {
+    // Makes sure the last frame is on the terminal before it is restored
+    fn drop(&mut self) {
+        self.frame_sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    is_synchronized_update_supported: bool,
+    // What is on the terminal now, to redraw only the changed symbols
+    previous_symbols: Vec<Symbol>,
+    // Reused by every frame
+    bytes: Vec<u8>,
+    // Kitty images stay on the terminal until they are deleted
+    is_kitty_image_shown: bool,
+}
+
+impl Writer {
+    fn write(&mut self, frame: &Frame) -> usize {
+        let mut bytes = take(&mut self.bytes);
+        bytes.clear();
+        if self.is_synchronized_update_supported {
+            let _ = write!(bytes, "{}", Escape::BeginSynchronizedUpdate);
+        }
+        let _ = write!(bytes, "{}", Escape::MakeCursorInvisible);
+        if frame.is_clear_needed {
+            let _ = write!(bytes, "{}", Escape::ClearScreen);
+            self.previous_symbols.clear();
+        }
+        if self.is_kitty_image_shown && frame.graphics_mode != GraphicsMode::Kitty {
+            bytes.extend(kitty::delete(KITTY_IMAGE_ID).as_bytes());
+            self.is_kitty_image_shown = false;
+        }
+        if let Some(image) = &frame.image {
+            let _ = write!(bytes, "{}", Escape::MoveCursorToStart);
+            match frame.graphics_mode {
+                GraphicsMode::Sixel => {
+                    bytes.extend(sixel::encode(image).as_bytes());
+                    // The image covers all symbols, so the overlay text is drawn every frame
+                    self.previous_symbols.clear();
+                }
+                GraphicsMode::Kitty => {
+                    let kitty = kitty::encode(image, KITTY_IMAGE_ID, frame.size_in_symbols);
+                    bytes.extend(kitty.as_bytes());
+                    self.is_kitty_image_shown = true;
+                }
+                GraphicsMode::Cells => (),
+            }
+        }
+        self.encode_symbols(frame, &mut bytes);
+        let _ = write!(bytes, "{}", Escape::MakeCursorVisible);
+        if self.is_synchronized_update_supported {
+            let _ = write!(bytes, "{}", Escape::EndSynchronizedUpdate);
+        }
+        // The terminal is restored and suspended with stdout locked too, so a frame is never cut
+        // off by that, except by a signal from outside. Once the terminal is restored, e.g. on
+        // panic, frames still queued are dropped.
+        let mut stdout = stdout().lock();
+        if terminal::is_restored() {
+            self.bytes = bytes;
+            return 0;
+        }
+        let _ = stdout.write_all(&bytes);
+        let _ = stdout.flush();
+        let byte_count = bytes.len();
+        self.bytes = bytes;
+        self.previous_symbols.clone_from(&frame.symbols);
+        byte_count
+    }
+
+    fn encode_symbols(&self, frame: &Frame, bytes: &mut Vec<u8>) {
+        let width = frame.size_in_symbols.x;
+        // Symbols without text are drawn only under the kitty image, which is placed over them
+        let draws_only_text = frame.graphics_mode == GraphicsMode::Sixel;
+        // Cursor is only known to be right at the symbol after a drawn one
+        let mut is_cursor_at_symbol = false;
+        let mut current_colors = None;
+        for (index, symbol) in frame.symbols.iter().enumerate() {
+            let is_hidden = draws_only_text && symbol.text.is_none();
+            if is_hidden || self.previous_symbols.get(index) == Some(symbol) {
+                is_cursor_at_symbol = false;
+                continue;
+            }
+            if !is_cursor_at_symbol {
+                let escape = if index == 0 {
+                    Escape::MoveCursorToStart
+                } else {
+                    Escape::MoveCursorTo {
+                        row: index / width,
+                        column: index % width,
+                    }
+                };
+                let _ = write!(bytes, "{escape}");
+                is_cursor_at_symbol = true;
+            }
+            let (background_color, foreground_color, text) = symbol.appearance();
+            let i = index % width;
+            let j = index / width;
+            // Both colors are samples for half blocks, otherwise the foreground is only for text
+            let (background_position, foreground_position) =
+                if symbol.top_color.is_some() && symbol.text.is_none() {
+                    (Some(vec2!(i, 2 * j + 1)), Some(vec2!(i, 2 * j)))
+                } else {
+                    (Some(vec2!(i, j)), None)
+                };
+            let color_mode = frame.color_mode;
+            let colors = (
+                color_mode.quantize(background_color, background_position),
+                color_mode.quantize(foreground_color, foreground_position),
+            );
+            let (current_background, current_foreground) = current_colors.unzip();
+            if frame.has_colors {
+                if current_background != Some(colors.0) {
+                    let _ = write!(bytes, "{}", Escape::SetBackgroundColor(colors.0));
+                }
+                if current_foreground != Some(colors.1) {
+                    let _ = write!(bytes, "{}", Escape::SetForegroundColor(colors.1));
+                }
+            }
+            current_colors = Some(colors);
+            bytes.extend(text.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+    }
+
+    fn finish(&mut self) {
+        let mut stdout = stdout().lock();
+        if self.is_kitty_image_shown && !terminal::is_restored() {
+            let _ = stdout.write_all(kitty::delete(KITTY_IMAGE_ID).as_bytes());
+            let _ = stdout.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        color::Color, color_mode::ColorMode, graphics_mode::GraphicsMode, math::vec2::vec2,
+        symbol::Symbol,
+    };
+
+    use super::{Frame, Writer};
+
+    #[test]
+    fn test() {
+        let frame = |colors: [Color; 6]| Frame {
+            symbols: colors.map(Symbol::with_color).to_vec(),
+            size_in_symbols: vec2!(3, 2),
+            image: None,
+            graphics_mode: GraphicsMode::Cells,
+            color_mode: ColorMode::TrueColor,
+            has_colors: true,
+            is_clear_needed: false,
+        };
+        let (black, red) = (Color::BLACK, Color::RED);
+        let mut writer = Writer::default();
+        // The first frame is drawn in one go, with colors set only once
+        let first = frame([black; 6]);
+        let mut bytes = vec![];
+        writer.encode_symbols(&first, &mut bytes);
+        assert_eq!(
+            String::from_utf8_lossy(&bytes),
+            "\x1b[H\x1b[48;2;0;0;0m\x1b[38;2;127;127;127m      "
+        );
+        // Only changed symbols are drawn, the cursor jumps over the unchanged ones, and colors
+        // are kept from the previous changed symbol
+        writer.previous_symbols = first.symbols;
+        let second = frame([black, red, red, black, black, red]);
+        let mut bytes = vec![];
+        writer.encode_symbols(&second, &mut bytes);
+        assert_eq!(
+            String::from_utf8_lossy(&bytes),
+            "\x1b[1;2H\x1b[48;2;255;0;0m\x1b[38;2;127;127;127m  \x1b[2;3H "
+        );
+        // Nothing is drawn when nothing has changed
+        writer.previous_symbols = second.symbols.clone();
+        let mut bytes = vec![];
+        writer.encode_symbols(&second, &mut bytes);
+        assert!(bytes.is_empty());
+    }
+}
@@ -0,0 +1,41 @@
+use std::fmt::{Display, Formatter, Result};
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub(crate) enum Projection {
+    // Rays spread from the camera position over the field of view
+    #[default]
+    Perspective,
+    // Rays are parallel to the view direction and start over the view height, so that sizes do
+    // not change with the distance
+    Orthographic,
+}
+
+impl Projection {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "perspective" => Some(Projection::Perspective),
+            "orthographic" => Some(Projection::Orthographic),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Projection::Perspective => "perspective",
+            Projection::Orthographic => "orthographic",
+        }
+    }
+
+    pub(crate) fn next(self) -> Self {
+        match self {
+            Projection::Perspective => Projection::Orthographic,
+            Projection::Orthographic => Projection::Perspective,
+        }
+    }
+}
+
+impl Display for Projection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str(self.name())
+    }
+}
@@ -1,54 +1,124 @@
 use crate::{
     ViewMode,
+    camera::Camera,
     color::Color,
+    consts::EPSILON,
+    geometry::bvh::Bvh,
     geometry::intersect::Intersect,
     geometry::intersection::Intersection,
     geometry::ray::Ray,
-    math::vec3::{Vec3f, vec3},
+    light::Light,
+    material::Material,
+    math::{
+        vec2::Vec2f,
+        vec3::{Vec3f, vec3},
+    },
     object::Object,
+    options::RenderOptions,
     palette::Palette,
     sky::Sky,
     trace_payload::TracePayload,
     trace_stats::TraceStats,
 };
 
+// Objects are collected first, so that the BVH is built once over all of them
+#[derive(Default)]
+pub(crate) struct SceneBuilder {
+    objects: Vec<Object>,
+    lights: Vec<Light>,
+    sky: Sky,
+}
+
+impl SceneBuilder {
+    pub(crate) fn spawn(mut self, object: Object) -> Self {
+        self.objects.push(object);
+        self
+    }
+
+    pub(crate) fn set_sky(mut self, sky: Sky) -> Self {
+        self.sky = sky;
+        self
+    }
+
+    pub(crate) fn add_light(mut self, light: Light) -> Self {
+        self.lights.push(light);
+        self
+    }
+
+    pub(crate) fn build(self) -> Scene {
+        let aabbs: Vec<_> = self.objects.iter().map(Object::aabb).collect();
+        Scene {
+            bvh: Bvh::build(&aabbs),
+            objects: self.objects,
+            lights: self.lights,
+            sky: self.sky,
+        }
+    }
+}
+
 pub(crate) struct Scene {
     objects: Vec<Object>,
+    bvh: Bvh,
+    lights: Vec<Light>,
     sky: Sky,
 }
 
 impl Scene {
-    pub(crate) fn new() -> Self {
-        Self {
-            objects: vec![],
-            sky: Sky::default(),
-        }
+    pub(crate) fn builder() -> SceneBuilder {
+        SceneBuilder::default()
     }
 
-    pub(crate) fn spawn(&mut self, object: Object) {
-        self.objects.push(object)
+    pub(crate) fn intersect(&self, ray: Ray) -> Option<(Intersection, &Material)> {
+        self.bvh
+            .intersect(ray, |index, ray| self.objects[index].intersect(ray))
+            .map(|(index, intersection)| (intersection, &self.objects[index].material))
     }
 
-    pub(crate) fn intersect(&self, ray: Ray) -> Option<(Intersection, Color)> {
-        self.objects
-            .iter()
-            .filter_map(|object| {
-                object
-                    .intersect(ray)
-                    .map(|intersection| (intersection, object.color))
-            })
-            .min_by(|(a, _), (b, _)| a.distance.total_cmp(&b.distance))
+    pub(crate) fn is_occluded(&self, ray: Ray, max_distance: f32) -> bool {
+        self.bvh.intersect_any(ray, max_distance, |index, ray| {
+            self.objects[index].intersect(ray)
+        })
+    }
+
+    // Traces the screen position, averaging over the lens samples when the camera has an aperture.
+    // The normal is the one of the first sample. Stats are not averaged, they count the rays of
+    // every sample, since that is the work done for the position.
+    pub(crate) fn trace_screen(
+        &self,
+        camera: &Camera,
+        position: Vec2f,
+        aspect_ratio: f32,
+        options: &RenderOptions,
+        stats: &mut TraceStats,
+    ) -> TracePayload {
+        let sample_count = if camera.aperture_radius > 0.0 {
+            options.lens_sample_count
+        } else {
+            1
+        };
+        let mut payload = self.trace(camera.screen_ray(position, aspect_ratio), options, stats);
+        for _ in 1..sample_count {
+            let sample = self.trace(camera.screen_ray(position, aspect_ratio), options, stats);
+            payload.color.0 += sample.color.0;
+        }
+        payload.color.0 = payload.color.0 / sample_count as f32;
+        payload
     }
 
-    pub(crate) fn trace(&self, ray: Ray, view_mode: &ViewMode) -> TracePayload {
-        const REFLECTION_DEPTH: usize = 2;
-        const REFLECTION_COUNT: usize = 2;
-        const MAX_REFLECTION_COUNT: usize = const {
+    // Stats are counted into the ones given, so that a whole tile shares them
+    pub(crate) fn trace(
+        &self,
+        ray: Ray,
+        options: &RenderOptions,
+        stats: &mut TraceStats,
+    ) -> TracePayload {
+        let view_mode = &options.view_mode;
+        let reflection_depth = options.reflection_depth;
+        let reflection_count = options.reflection_count;
+        let max_reflection_count = {
             let mut count = 1; // one is an initial ray
-            let mut depth = 1;
-            while depth <= REFLECTION_DEPTH {
-                count += REFLECTION_COUNT.pow(depth as u32);
-                depth += 1;
+            for depth in 1..=reflection_depth {
+                count += reflection_count.pow(depth as u32);
             }
             count
         };
@@ -56,95 +126,124 @@ impl Scene {
         struct Incident {
             ray: Ray,
             depth: usize,
+            // How much of the light this ray carries reaches the viewer
+            attenuation: Vec3f,
         }
 
-        let mut incidents = vec![Incident { ray, depth: 0 }];
-        let mut colors = vec![];
-        let mut stats = TraceStats::default();
+        let mut incidents = vec![Incident {
+            ray,
+            depth: 0,
+            attenuation: vec3!(1.0),
+        }];
+        let mut color = vec3!(0.0);
+        let mut normal = None;
+        // Only the first trace into the stats allocates the per light counts
+        if stats.light_shadow_traced.len() < self.lights.len() {
+            stats.light_shadow_traced.resize(self.lights.len(), 0);
+        }
+        let traced_before = stats.traced;
         while let Some(incident) = incidents.pop() {
             stats.traced += 1;
-            let ray = incident.ray;
-            let depth = incident.depth;
-            let color = if let Some((intersection, color)) = self.intersect(ray) {
-                stats.hit += 1;
-                match view_mode {
-                    ViewMode::Normal => {
-                        let color = 0.5 * intersection.normal + 0.5;
-                        colors.push(color);
-                        break;
-                    }
-                    ViewMode::Depth => {
-                        const FAR_DISTANCE: f32 = 4.0;
-                        let ratio = intersection.distance / FAR_DISTANCE;
-                        let color = vec3!(ratio);
-                        colors.push(color);
-                        break;
-                    }
-                    _ => (),
-                }
-                let hit_position = intersection.hit_position(ray);
-                if incident.depth < REFLECTION_DEPTH {
-                    for _ in 0..REFLECTION_COUNT {
-                        let reflected_ray = Ray {
-                            origin: hit_position,
-                            direction: generate_diffuse_ray(
-                                ray.direction,
-                                intersection.normal,
-                                0.1,
-                            ),
-                        };
-                        incidents.push(Incident {
-                            depth: depth + 1,
-                            ray: reflected_ray,
-                        });
-                        stats.reflected += 1;
-                    }
-                }
-                let shadow_ray = Ray {
-                    origin: hit_position,
-                    direction: -self.sky.sun_light_direction,
-                };
-                stats.shadow_traced += 1;
-                // Let the sun to light with 1.0 intensity,
-                // but leave some threshold for ambient light
-                const AMBIENT_LIGHT_THRESHOLD: f32 = 0.2;
-                if self.intersect(shadow_ray).is_some() {
-                    stats.shadow_hit += 1;
-                    AMBIENT_LIGHT_THRESHOLD * color.0
-                } else {
-                    let light_intensity = intersection
-                        .normal
-                        .dot(-self.sky.sun_light_direction)
-                        .max(AMBIENT_LIGHT_THRESHOLD);
-                    color.0 * light_intensity
-                }
-            } else {
-                match view_mode {
+            let Incident {
+                ray,
+                depth,
+                attenuation,
+            } = incident;
+            let Some((intersection, material)) = self.intersect(ray) else {
+                let sky_color = match view_mode {
                     ViewMode::Color => self.sky.get_color(ray.direction).0,
                     ViewMode::Normal => 0.5 * -ray.direction + 0.5,
                     ViewMode::Depth => vec3!(1.0),
                     ViewMode::Complexity => vec3!(0.0),
+                };
+                color += attenuation * sky_color;
+                continue;
+            };
+            stats.hit += 1;
+            if depth == 0 {
+                normal = Some(intersection.normal);
+            }
+            match view_mode {
+                ViewMode::Normal => {
+                    color = 0.5 * intersection.normal + 0.5;
+                    break;
+                }
+                ViewMode::Depth => {
+                    const FAR_DISTANCE: f32 = 4.0;
+                    let ratio = intersection.distance / FAR_DISTANCE;
+                    color = vec3!(ratio);
+                    break;
                 }
+                _ => (),
+            }
+            color += attenuation * material.emitted();
+            let scatter_count = if depth < reflection_depth {
+                reflection_count
+            } else {
+                0
             };
-            let color_contribution = 0.1f32.powi(depth as i32);
-            colors.push(color * color_contribution);
+            for scattered in material
+                .scatter(ray, &intersection, scatter_count)
+                .into_iter()
+                .flatten()
+            {
+                incidents.push(Incident {
+                    ray: scattered.ray,
+                    depth: depth + 1,
+                    attenuation: attenuation * scattered.attenuation,
+                });
+                stats.reflected += 1;
+            }
+            let Some(albedo) = material.diffuse_albedo() else {
+                continue;
+            };
+            let hit_position = intersection.hit_position(ray);
+            let shadow_ray = Ray {
+                origin: hit_position,
+                direction: -self.sky.sun_light_direction,
+            };
+            stats.shadow_traced += 1;
+            // Let the sun to light with 1.0 intensity,
+            // but leave some threshold for ambient light
+            const AMBIENT_LIGHT_THRESHOLD: f32 = 0.2;
+            let light_intensity = if self.is_occluded(shadow_ray, f32::INFINITY) {
+                stats.shadow_hit += 1;
+                AMBIENT_LIGHT_THRESHOLD
+            } else {
+                intersection
+                    .normal
+                    .dot(-self.sky.sun_light_direction)
+                    .max(AMBIENT_LIGHT_THRESHOLD)
+            };
+            color += attenuation * albedo.0 * light_intensity;
+            for (light_index, light) in self.lights.iter().enumerate() {
+                for _ in 0..light.sample_count() {
+                    let sample = light.sample(hit_position);
+                    let cos = intersection.normal.dot(sample.direction);
+                    if cos <= 0.0 {
+                        continue;
+                    }
+                    let shadow_ray = Ray {
+                        origin: hit_position,
+                        direction: sample.direction,
+                    };
+                    stats.shadow_traced += 1;
+                    stats.light_shadow_traced[light_index] += 1;
+                    if self.is_occluded(shadow_ray, sample.distance - EPSILON) {
+                        stats.shadow_hit += 1;
+                        continue;
+                    }
+                    color += attenuation * albedo.0 * sample.radiance * cos;
+                }
+            }
         }
         let color = if let ViewMode::Complexity = view_mode {
-            let ratio = colors.len() as f32 / MAX_REFLECTION_COUNT as f32;
-            Palette::TEMPERATURE.get_color(ratio)
+            let traced = stats.traced - traced_before;
+            let ratio = traced as f32 / max_reflection_count as f32;
+            Palette::TEMPERATURE.get_color(ratio.min(1.0))
         } else {
-            Color(colors.into_iter().sum::<Vec3f>())
+            Color(color)
         };
-        TracePayload { color, stats }
+        TracePayload { color, normal }
     }
 }
-
-fn generate_diffuse_ray(incident: Vec3f, normal: Vec3f, bias: f32) -> Vec3f {
-    let bias_direction = loop {
-        let random_unit = Vec3f::random_unit();
-        if random_unit.dot(normal) > 0.0 {
-            break random_unit.normalize();
-        };
-    };
-    (incident.reflect(normal) + bias_direction * bias).normalize()
-}
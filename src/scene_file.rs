@@ -0,0 +1,408 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::read_to_string,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    camera::{Camera, MAX_FOV, MIN_FOV},
+    geometry::{aabb::Aabb, sphere::Sphere, triangular::Triangular},
+    light::{Light, LightShape},
+    material::Material,
+    obj::parse_obj,
+    object::Object,
+    projection::Projection,
+    scene::Scene,
+    sky::Sky,
+    text_parser::{LineTokens, ParseError},
+};
+
+pub(crate) struct SceneFile {
+    pub(crate) scene: Scene,
+    pub(crate) camera: Camera,
+}
+
+pub(crate) enum SceneFileError {
+    Io { path: PathBuf, error: io::Error },
+    Parse { path: PathBuf, error: ParseError },
+}
+
+impl Display for SceneFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneFileError::Io { path, error } => {
+                f.write_fmt(format_args!("{}: {error}", path.display()))
+            }
+            SceneFileError::Parse { path, error } => {
+                f.write_fmt(format_args!("{}: {error}", path.display()))
+            }
+        }
+    }
+}
+
+// Every line is a statement, and the first token tells its kind:
+//
+// sun <direction>
+// camera <look from> <look at>
+// projection perspective <vertical field of view angle>
+// projection orthographic <view height>
+// roll <camera roll angle>
+// near <camera near clip distance>
+// aperture <camera lens radius>
+// focus <camera focus distance, or auto to focus on the screen center>
+// sphere <center> <radius> <material>
+// box <min> <max> <material>
+// mesh <OBJ path relative to the scene file> <material>
+// light point <color> <intensity> <position>
+// light spot <color> <intensity> <position> <direction> <cone angle> <falloff angle>
+// light rectangle <color> <intensity> <corner> <edge u> <edge v>
+// light sphere <color> <intensity> <center> <radius>
+//
+// Materials are one of:
+//
+// lambertian <albedo>
+// metal <albedo> <roughness>
+// dielectric <refraction index>
+// emissive <color>
+//
+// Vectors are three numbers, colors are #rrggbb, angles are in degrees. Lines starting with #
+// are comments.
+pub(crate) fn load_scene_file(path: &Path) -> Result<SceneFile, SceneFileError> {
+    let text = read_file(path)?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    parse_scene(&text, |mesh_path| {
+        let mesh_path = directory.join(mesh_path);
+        let text = read_file(&mesh_path)?;
+        parse_obj(&text).map_err(|error| SceneFileError::Parse {
+            path: mesh_path,
+            error,
+        })
+    })
+    .map_err(|error| match error {
+        ParseOrLoadError::Parse(error) => SceneFileError::Parse {
+            path: path.to_owned(),
+            error,
+        },
+        ParseOrLoadError::Load(error) => error,
+    })
+}
+
+fn read_file(path: &Path) -> Result<String, SceneFileError> {
+    read_to_string(path).map_err(|error| SceneFileError::Io {
+        path: path.to_owned(),
+        error,
+    })
+}
+
+enum ParseOrLoadError {
+    Parse(ParseError),
+    Load(SceneFileError),
+}
+
+impl From<ParseError> for ParseOrLoadError {
+    fn from(error: ParseError) -> Self {
+        ParseOrLoadError::Parse(error)
+    }
+}
+
+fn parse_scene<F>(text: &str, load_mesh: F) -> Result<SceneFile, ParseOrLoadError>
+where
+    F: Fn(&str) -> Result<Triangular, SceneFileError>,
+{
+    let mut scene = Scene::builder();
+    let mut camera = Camera::default();
+    // Unless given, the focus is on the camera target
+    let mut focus_distance = None;
+    for (line_index, line) in text.lines().enumerate() {
+        let mut tokens = LineTokens::new(line_index, line);
+        if tokens.is_empty() {
+            continue;
+        }
+        let (column, statement) = tokens.next("statement")?;
+        match statement {
+            _ if statement.starts_with('#') => continue,
+            "sun" => {
+                let sun_light_direction = tokens.next_direction("sun direction")?;
+                scene = scene.set_sky(Sky {
+                    sun_light_direction,
+                });
+            }
+            "camera" => {
+                camera.look_from = tokens.next_vec3("camera position")?;
+                camera.look_at = tokens.next_vec3("camera target")?;
+            }
+            "projection" => {
+                let (column, name) = tokens.next("projection")?;
+                camera.projection = Projection::from_name(name).ok_or_else(|| {
+                    tokens.error(column, format!("unknown projection \"{name}\""))
+                })?;
+                match camera.projection {
+                    Projection::Perspective => {
+                        // Limits are checked in whole degrees, since they are not exact in radians
+                        let (min, max) =
+                            (MIN_FOV.to_degrees().round(), MAX_FOV.to_degrees().round());
+                        let requirement = format!("from {min} to {max} degrees");
+                        let fov = tokens.next_f32_where("field of view", &requirement, |fov| {
+                            (min..=max).contains(&fov)
+                        })?;
+                        camera.vertical_fov = fov.to_radians().clamp(MIN_FOV, MAX_FOV);
+                    }
+                    Projection::Orthographic => {
+                        camera.view_height =
+                            tokens.next_f32_where("view height", "greater than 0", is_positive)?
+                    }
+                }
+            }
+            "roll" => camera.roll = tokens.next_f32("roll angle")?.to_radians(),
+            "near" => {
+                camera.near_clip =
+                    tokens.next_f32_where("near clip distance", "of at least 0", is_non_negative)?
+            }
+            "aperture" => {
+                camera.aperture_radius =
+                    tokens.next_f32_where("aperture radius", "of at least 0", is_non_negative)?
+            }
+            "focus" => {
+                let (column, token) = tokens.next("focus distance")?;
+                camera.has_autofocus = token == "auto";
+                if !camera.has_autofocus {
+                    let distance = token.parse().ok().filter(|&distance| is_positive(distance));
+                    let distance = distance.ok_or_else(|| {
+                        tokens.error(
+                            column,
+                            format!("expected focus distance greater than 0, found \"{token}\""),
+                        )
+                    })?;
+                    focus_distance = Some(distance);
+                }
+            }
+            "sphere" => {
+                let center = tokens.next_vec3("sphere center")?;
+                let radius =
+                    tokens.next_f32_where("sphere radius", "greater than 0", is_positive)?;
+                let material = parse_material(&mut tokens)?;
+                scene = scene.spawn(Object {
+                    material,
+                    intersect: Box::new(Sphere { center, radius }),
+                });
+            }
+            "box" => {
+                let min = tokens.next_vec3("box min corner")?;
+                let max = tokens.next_vec3("box max corner")?;
+                let material = parse_material(&mut tokens)?;
+                scene = scene.spawn(Object {
+                    material,
+                    intersect: Box::new(Aabb {
+                        min: min.min(max),
+                        max: min.max(max),
+                    }),
+                });
+            }
+            "mesh" => {
+                let (_, path) = tokens.next("mesh path")?;
+                let material = parse_material(&mut tokens)?;
+                let triangular = load_mesh(path).map_err(ParseOrLoadError::Load)?;
+                scene = scene.spawn(Object {
+                    material,
+                    intersect: Box::new(triangular),
+                });
+            }
+            "light" => scene = scene.add_light(parse_light(&mut tokens)?),
+            _ => {
+                return Err(tokens
+                    .error(column, format!("unknown statement \"{statement}\""))
+                    .into());
+            }
+        }
+        tokens.finish()?;
+    }
+    camera.focus_distance =
+        focus_distance.unwrap_or_else(|| (camera.look_at - camera.look_from).length());
+    Ok(SceneFile {
+        scene: scene.build(),
+        camera,
+    })
+}
+
+fn is_positive(value: f32) -> bool {
+    value > 0.0
+}
+
+fn is_non_negative(value: f32) -> bool {
+    value >= 0.0
+}
+
+fn parse_material(tokens: &mut LineTokens) -> Result<Material, ParseError> {
+    let (column, kind) = tokens.next("material")?;
+    let material = match kind {
+        "lambertian" => Material::Lambertian {
+            albedo: tokens.next_color("albedo")?,
+        },
+        "metal" => Material::Metal {
+            albedo: tokens.next_color("albedo")?,
+            roughness: tokens.next_f32_where("roughness", "of at least 0", is_non_negative)?,
+        },
+        "dielectric" => Material::Dielectric {
+            refraction_index: tokens.next_f32_where(
+                "refraction index",
+                "greater than 0",
+                is_positive,
+            )?,
+        },
+        "emissive" => Material::Emissive {
+            color: tokens.next_color("color")?,
+        },
+        _ => return Err(tokens.error(column, format!("unknown material \"{kind}\""))),
+    };
+    Ok(material)
+}
+
+fn parse_light(tokens: &mut LineTokens) -> Result<Light, ParseError> {
+    let (column, kind) = tokens.next("light kind")?;
+    let color = tokens.next_color("light color")?;
+    let intensity = tokens.next_f32_where("light intensity", "of at least 0", is_non_negative)?;
+    let shape = match kind {
+        "point" => LightShape::Point {
+            position: tokens.next_vec3("light position")?,
+        },
+        "spot" => {
+            let position = tokens.next_vec3("light position")?;
+            let direction = tokens.next_direction("light direction")?;
+            let cone_angle = tokens.next_f32_where("cone angle", "from 0 to 180", |angle| {
+                angle > 0.0 && angle <= 180.0
+            })?;
+            // The light fades out within the cone, so a wider falloff would start outside of it
+            let falloff =
+                tokens.next_f32_where("falloff angle", "from 0 to the cone angle", |angle| {
+                    (0.0..=cone_angle).contains(&angle)
+                })?;
+            LightShape::Spot {
+                position,
+                direction,
+                cone_angle: cone_angle.to_radians(),
+                falloff: falloff.to_radians(),
+            }
+        }
+        "rectangle" => LightShape::Rectangle {
+            corner: tokens.next_vec3("rectangle corner")?,
+            edge_u: tokens.next_vec3("rectangle edge")?,
+            edge_v: tokens.next_vec3("rectangle edge")?,
+        },
+        "sphere" => LightShape::Sphere {
+            center: tokens.next_vec3("sphere center")?,
+            radius: tokens.next_f32_where("sphere radius", "greater than 0", is_positive)?,
+        },
+        _ => return Err(tokens.error(column, format!("unknown light \"{kind}\""))),
+    };
+    Ok(Light {
+        color,
+        intensity,
+        shape,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{geometry::triangular::Triangular, projection::Projection};
+
+    use super::{ParseOrLoadError, parse_scene};
+
+    #[test]
+    fn test() {
+        let load_mesh = |_: &str| Ok(Triangular::builder().build());
+        let text = "# comment\n\nsun -1 -1 -1\nsphere 0 0 0 0.5 lambertian #ff0000\nmesh a.obj metal #00ff00 0.1\n";
+        assert!(parse_scene(text, load_mesh).is_ok());
+        let text = "camera 0 0 2 0 0 0\nprojection orthographic 4\nroll 90\nnear 0.5\n";
+        let Ok(scene_file) = parse_scene(text, load_mesh) else {
+            panic!("camera settings are expected to parse");
+        };
+        assert!(scene_file.camera.projection == Projection::Orthographic);
+        assert_eq!(scene_file.camera.view_height, 4.0);
+        assert_eq!(scene_file.camera.near_clip, 0.5);
+        assert_eq!(scene_file.camera.focus_distance, 2.0);
+        let text = "aperture 0.1\nfocus auto\n";
+        assert!(
+            parse_scene(text, load_mesh).is_ok_and(|scene_file| scene_file.camera.has_autofocus)
+        );
+        let text = "focus far";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("invalid focus distance is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 7));
+        let text = "focus 0";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("zero focus distance is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 7));
+        let text = "projection fisheye 180";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("unknown projection is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 12));
+        let text = "projection perspective 5\nprojection perspective 170\n";
+        assert!(parse_scene(text, load_mesh).is_ok());
+        let text = "projection perspective 180";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("too wide field of view is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 24));
+        let text = "near -1";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("negative near clip distance is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 6));
+        let text = "sun -1 -1 -1\n  sphere 0 0 zero 0.5 lambertian #ff0000\n";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("invalid number is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (2, 14));
+        let text = "box 0 0 0 1 1 1 glass";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("unknown material is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 17));
+        let text = "light point #ffffff 1";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("missing position is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 22));
+        let text = "sun 0 0 0";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("zero direction is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 5));
+        let text = "sphere 0 0 0 -1 dielectric 1.5";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("negative radius is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 14));
+        let text = "sphere 0 0 0 1 dielectric 0";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("zero refraction index is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 27));
+        let text = "box 0 0 0 1 1 1 metal #ffffff -0.1";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("negative roughness is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 31));
+        let text = "light point #ffffff -1 0 0 0";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("negative intensity is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 21));
+        let text = "light spot #ffffff 1 0 0 0 0 -1 0 30 10";
+        assert!(parse_scene(text, load_mesh).is_ok());
+        let text = "light spot #ffffff 1 0 0 0 0 -1 0 0 0";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("zero cone angle is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 35));
+        let text = "light spot #ffffff 1 0 0 0 0 -1 0 30 40";
+        let Err(ParseOrLoadError::Parse(error)) = parse_scene(text, load_mesh) else {
+            panic!("falloff wider than the cone is expected to fail");
+        };
+        assert_eq!((error.line, error.column), (1, 38));
+    }
+}
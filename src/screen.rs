@@ -1,127 +1,286 @@
-use std::{num::NonZero, sync::mpsc};
+use std::{mem::take, sync::Mutex};
 
 use libc::{STDOUT_FILENO, TIOCGWINSZ, ioctl, winsize};
 
 use crate::{
-    ViewMode,
     camera::Camera,
-    escape::Escape,
+    color::Color,
+    graphics_mode::GraphicsMode,
+    image::Image,
     math::vec2::{Vec2f, Vec2u, vec2},
+    math::vec3::Vec3f,
+    options::RenderOptions,
+    output::{Frame, Output},
     scene::Scene,
     symbol::Symbol,
-    trace_payload::TracePayload,
+    symbol_mode::SymbolMode,
+    terminal,
     trace_stats::TraceStats,
+    util::{pcg, thread_pool::ThreadPool},
 };
 
+// Neighbor surfaces with normals diverging more than this cosine are considered an edge
+const EDGE_COS: f32 = 0.8;
+
+// What symbols are made of
+#[derive(Clone, Copy, Default)]
+struct Sample {
+    color: Color,
+    normal: Option<Vec3f>,
+}
+
 pub(crate) struct Screen {
     size_in_symbols: Vec2u,
     size_in_pixels: Vec2u,
     symbols: Vec<Symbol>,
+    // Reused by every frame
+    samples: Vec<Sample>,
+    // Seeds the random generators together with the pixel indices
+    frame_index: u64,
+    pool: ThreadPool,
     overlay_text_lines: Vec<String>,
+    emitted_byte_count: usize,
+    supported_graphics_modes: Vec<GraphicsMode>,
+    // What the current frame is drawn with after falling back from unsupported graphics
+    graphics_mode: GraphicsMode,
+    // Frame rendered at pixel resolution when graphics are used instead of cells
+    image: Option<Image>,
+    // After a resize or a graphics mode switch, the leftovers are cleared before the next frame
+    is_clear_needed: bool,
+    output: Output,
 }
 
 impl Screen {
-    pub(crate) fn new() -> Self {
-        let mut winsize = winsize {
-            ws_row: 0,
-            ws_col: 0,
-            ws_xpixel: 0,
-            ws_ypixel: 0,
-        };
-        assert_eq!(unsafe { ioctl(STDOUT_FILENO, TIOCGWINSZ, &mut winsize) }, 0);
-        let size_in_symbols = vec2!(winsize.ws_col as usize, winsize.ws_row as usize);
-        let size_in_pixels = if winsize.ws_xpixel != 0 && winsize.ws_ypixel != 0 {
-            vec2!(winsize.ws_xpixel as usize, winsize.ws_ypixel as usize)
-        } else {
-            // TODO: Search for other ways to know the font size
-            //
-            // For now, let's fall back to VGA 437 8x16 font
-            // https://en.wikipedia.org/wiki/Code_page_437
-            // https://wiki.archlinux.org/title/Linux_console#Fonts
-            let font_size = vec2!(8, 16);
-            font_size.hadamard(size_in_symbols)
-        };
+    pub(crate) fn new(thread_count: usize) -> Self {
+        let winsize = query_winsize();
+        let (size_in_symbols, size_in_pixels) = sizes(&winsize);
+        let is_pixel_size_known = winsize.ws_xpixel != 0 && winsize.ws_ypixel != 0;
         let colors = vec![Symbol::default(); size_in_symbols.area()];
+        // Graphics can't be placed over cells without knowing the real pixel size
+        let supported_graphics_modes = GraphicsMode::ALL
+            .into_iter()
+            .filter(|mode| {
+                *mode == GraphicsMode::Cells || is_pixel_size_known && mode.is_supported()
+            })
+            .collect();
         Self {
             size_in_symbols,
             size_in_pixels,
             symbols: colors,
+            samples: vec![],
+            frame_index: 0,
+            pool: ThreadPool::new(thread_count),
             overlay_text_lines: vec![],
+            emitted_byte_count: 0,
+            supported_graphics_modes,
+            graphics_mode: GraphicsMode::Cells,
+            image: None,
+            is_clear_needed: false,
+            // Frames are wrapped in synchronized updates to avoid tearing
+            output: Output::new(terminal::is_synchronized_update_supported()),
+        }
+    }
+
+    // The terminal may be resized at any moment, so the size is checked before every frame, and
+    // after a suspension everything is redrawn
+    fn update_size(&mut self) {
+        if terminal::take_redraw_request() {
+            self.is_clear_needed = true;
+        }
+        let (size_in_symbols, size_in_pixels) = sizes(&query_winsize());
+        if size_in_symbols == self.size_in_symbols && size_in_pixels == self.size_in_pixels {
+            return;
         }
+        self.size_in_symbols = size_in_symbols;
+        self.size_in_pixels = size_in_pixels;
+        self.symbols = vec![Symbol::default(); size_in_symbols.area()];
+        // Terminals rewrap or crop the old content, so none of it can be trusted
+        self.is_clear_needed = true;
     }
 
-    pub(crate) fn render(&mut self, scene: &Scene, camera: &Camera, view_mode: &ViewMode) {
+    pub(crate) fn render(&mut self, scene: &Scene, camera: &Camera, options: &RenderOptions) {
+        self.update_size();
+        self.frame_index += 1;
         let size_in_symbols = self.size_in_symbols;
+        // Unsupported graphics fall back to cells
+        let graphics_mode = Some(options.graphics_mode)
+            .filter(|mode| self.supported_graphics_modes.contains(mode))
+            .unwrap_or_default();
+        // Images leave pixels under cells that the writer would take as unchanged, so switching
+        // clears the screen, which makes the writer forget the symbols it has drawn too
+        if graphics_mode != self.graphics_mode {
+            self.is_clear_needed = true;
+        }
+        self.graphics_mode = graphics_mode;
         self.overlay_text_lines.push(format!(
-            "Symbol size: {}x{} ({} total)",
+            "Symbol size: {}x{} ({} total, {} symbols, {} colors, {} graphics{})",
             size_in_symbols.x,
             size_in_symbols.y,
-            size_in_symbols.area()
+            size_in_symbols.area(),
+            options.symbol_mode,
+            options.color_mode,
+            graphics_mode,
+            if graphics_mode == options.graphics_mode {
+                String::new()
+            } else {
+                format!(", {} is unsupported", options.graphics_mode)
+            }
         ));
+        if graphics_mode != GraphicsMode::Cells {
+            self.render_image(scene, camera, options);
+            return;
+        }
+        self.image = None;
         let size_in_pixels = self.size_in_pixels;
-        let thread_count = std::thread::available_parallelism()
-            .map(NonZero::get)
-            .unwrap_or(1);
-        let symbol_count = size_in_symbols.area();
-        let task_count = symbol_count.next_multiple_of(thread_count);
-        let tasks_per_thread = task_count / thread_count;
-        let (sender, receiver) = mpsc::channel();
-        std::thread::scope(|scope| {
-            for thread_index in 0..thread_count {
-                let sender = sender.clone();
-                scope.spawn(move || {
-                    let start_index = thread_index * tasks_per_thread;
-                    for task_index in start_index..start_index + tasks_per_thread {
-                        if task_index >= symbol_count {
-                            break;
-                        }
-                        let i = task_index % size_in_symbols.x;
-                        let j = task_index / size_in_symbols.x;
-                        let index = vec2!(i, j);
-                        let position = Vec2f::from(index) / Vec2f::from(size_in_symbols);
-                        let mut viewport_position = 2.0 * position - 1.0;
-                        viewport_position.y = -viewport_position.y;
-                        let aspect_ratio = size_in_pixels.x as f32 / size_in_pixels.y as f32;
-                        viewport_position.x *= aspect_ratio;
-                        let viewport_ray = camera.viewport_ray(viewport_position);
-                        let payload = scene.trace(viewport_ray, view_mode);
-                        sender.send((task_index, payload)).unwrap();
-                    }
-                });
+        let samples_per_symbol = options.symbol_mode.samples_per_symbol();
+        let size_in_samples = vec2!(size_in_symbols.x, samples_per_symbol * size_in_symbols.y);
+        let aspect_ratio = size_in_pixels.x as f32 / size_in_pixels.y as f32;
+        let mut samples = take(&mut self.samples);
+        samples.resize(size_in_samples.area(), Sample::default());
+        let overall_stats = Mutex::new(TraceStats::default());
+        let frame_index = self.frame_index;
+        self.pool.run_over_tiles(&mut samples, |start_index, tile| {
+            let mut stats = TraceStats::default();
+            for (offset, sample) in tile.iter_mut().enumerate() {
+                let index = start_index + offset;
+                pcg::seed(options.seed, frame_index, index as u64);
+                let index = vec2!(index % size_in_samples.x, index / size_in_samples.x);
+                // Samples are normalized, so the aspect ratio stays the same regardless of how
+                // many samples a symbol holds
+                let position = Vec2f::from(index) / Vec2f::from(size_in_samples);
+                let payload =
+                    scene.trace_screen(camera, position, aspect_ratio, options, &mut stats);
+                *sample = Sample {
+                    color: payload.color,
+                    normal: payload.normal,
+                };
             }
+            *overall_stats.lock().unwrap() += stats;
         });
-        let mut overall_stats = TraceStats::default();
-        for _ in 0..symbol_count {
-            let (index, TracePayload { color, stats }) = receiver.recv().unwrap();
-            self.symbols[index] = Symbol::with_color(color);
-            overall_stats += stats;
+        let overall_stats = overall_stats.into_inner().unwrap();
+        let (right, up, _) = camera.basis();
+        for (index, symbol) in self.symbols.iter_mut().enumerate() {
+            *symbol = match options.symbol_mode {
+                SymbolMode::Full => Symbol::with_color(samples[index].color),
+                SymbolMode::HalfBlock => {
+                    let i = index % size_in_symbols.x;
+                    let j = index / size_in_symbols.x;
+                    let top_index = i + size_in_samples.x * 2 * j;
+                    let bottom_index = top_index + size_in_samples.x;
+                    Symbol::with_colors(samples[top_index].color, samples[bottom_index].color)
+                }
+                SymbolMode::Ascii => {
+                    let i = index % size_in_symbols.x;
+                    let j = index / size_in_symbols.x;
+                    let normal = samples[index].normal;
+                    // The symbol is on an edge when the surface differs from the next symbol
+                    // to the right or below
+                    let is_edge = normal.is_some_and(|normal| {
+                        let neighbors = [
+                            (i + 1 < size_in_symbols.x, index + 1),
+                            (j + 1 < size_in_symbols.y, index + size_in_symbols.x),
+                        ];
+                        neighbors.into_iter().any(|(exists, neighbor_index)| {
+                            exists
+                                && samples[neighbor_index]
+                                    .normal
+                                    .is_none_or(|neighbor| neighbor.dot(normal) < EDGE_COS)
+                        })
+                    });
+                    match normal {
+                        Some(normal) if is_edge => {
+                            Symbol::with_edge(vec2!(normal.dot(right), normal.dot(up)))
+                        }
+                        _ => Symbol::with_luminance(samples[index].color),
+                    }
+                }
+            };
         }
+        self.samples = samples;
         self.overlay_text_lines.push(overall_stats.to_string());
     }
 
+    fn render_image(&mut self, scene: &Scene, camera: &Camera, options: &RenderOptions) {
+        let size = if self.graphics_mode == GraphicsMode::Sixel {
+            // The bottom row is left out so that the terminal does not scroll after the image,
+            // and Sixel images are drawn in bands of 6 pixels
+            let symbol_height = self.size_in_pixels.y / self.size_in_symbols.y.max(1);
+            let height = self.size_in_symbols.y.saturating_sub(1) * symbol_height;
+            vec2!(self.size_in_pixels.x, height / 6 * 6)
+        } else {
+            self.size_in_pixels
+        };
+        let (image, stats) = Image::render_with_stats(
+            &mut self.pool,
+            scene,
+            camera,
+            options,
+            size,
+            self.frame_index,
+        );
+        self.image = Some(image);
+        self.symbols.fill(Symbol::default());
+        self.overlay_text_lines.push(stats.to_string());
+    }
+
     pub(crate) fn append_overlay_text_line(&mut self, line: String) {
         self.overlay_text_lines.push(line);
     }
 
-    pub(crate) fn draw(&mut self) {
-        print!(
-            "{}{}",
-            Escape::MakeCursorInvisible,
-            Escape::MoveCursorToStart
-        );
-        for (j, line) in self.overlay_text_lines.iter().enumerate() {
-            for (i, text) in line.chars().enumerate() {
+    pub(crate) fn draw(&mut self, options: &RenderOptions) {
+        self.overlay_text_lines
+            .push(format!("{} bytes emitted", self.emitted_byte_count));
+        // Text that does not fit the screen is cut off
+        let visible_lines = self.overlay_text_lines.iter().take(self.size_in_symbols.y);
+        for (j, line) in visible_lines.enumerate() {
+            for (i, text) in line.chars().take(self.size_in_symbols.x).enumerate() {
                 let index = i + self.size_in_symbols.x * j;
                 let _ = self.symbols[index].text.insert(text);
             }
         }
         self.overlay_text_lines.clear();
-        for j in 0..self.size_in_symbols.y {
-            for i in 0..self.size_in_symbols.x {
-                let index = i + self.size_in_symbols.x * j;
-                print!("{}", self.symbols[index].encode());
-            }
-        }
-        print!("{}", Escape::MakeCursorVisible);
+        let frame = Frame {
+            symbols: take(&mut self.symbols),
+            size_in_symbols: self.size_in_symbols,
+            image: self.image.take(),
+            graphics_mode: self.graphics_mode,
+            color_mode: options.color_mode,
+            has_colors: options.symbol_mode.has_colors(),
+            is_clear_needed: self.is_clear_needed,
+        };
+        self.is_clear_needed = false;
+        let (mut symbols, emitted_byte_count) = self.output.write(frame);
+        // Symbols of the frame before might be of another size
+        symbols.resize(self.size_in_symbols.area(), Symbol::default());
+        self.symbols = symbols;
+        self.emitted_byte_count = emitted_byte_count;
     }
 }
+
+fn query_winsize() -> winsize {
+    let mut winsize = winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    assert_eq!(unsafe { ioctl(STDOUT_FILENO, TIOCGWINSZ, &mut winsize) }, 0);
+    winsize
+}
+
+// Size in symbols and in pixels
+fn sizes(winsize: &winsize) -> (Vec2u, Vec2u) {
+    let size_in_symbols = vec2!(winsize.ws_col as usize, winsize.ws_row as usize);
+    let size_in_pixels = if winsize.ws_xpixel != 0 && winsize.ws_ypixel != 0 {
+        vec2!(winsize.ws_xpixel as usize, winsize.ws_ypixel as usize)
+    } else {
+        // TODO: Search for other ways to know the font size
+        //
+        // For now, let's fall back to VGA 437 8x16 font
+        // https://en.wikipedia.org/wiki/Code_page_437
+        // https://wiki.archlinux.org/title/Linux_console#Fonts
+        let font_size = vec2!(8, 16);
+        font_size.hadamard(size_in_symbols)
+    };
+    (size_in_symbols, size_in_pixels)
+}
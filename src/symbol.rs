@@ -1,8 +1,18 @@
-use crate::{Color, escape::Escape};
+use crate::{
+    Color,
+    math::{vec2::Vec2f, vec3::vec3},
+};
+
+// Upper half block, https://en.wikipedia.org/wiki/Block_Elements
+const UPPER_HALF_BLOCK: char = '\u{2580}';
+// From the darkest to the brightest
+const LUMINANCE_RAMP: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
 
 #[derive(Clone, Default)]
 pub(crate) struct Symbol {
     pub(crate) color: Color,
+    // Color of the upper half of the symbol when it holds two samples
+    pub(crate) top_color: Option<Color>,
     pub(crate) text: Option<char>,
 }
 
@@ -14,13 +24,57 @@ impl Symbol {
         }
     }
 
-    pub(crate) fn encode(&self) -> String {
-        let text = self.text.unwrap_or(' ');
-        format!(
-            "{}{}{}",
-            Escape::SetBackgroundColor(self.color),
-            Escape::SetForegroundColor(self.color.inverted()),
-            text
-        )
+    pub(crate) fn with_colors(top_color: Color, bottom_color: Color) -> Self {
+        Self {
+            color: bottom_color,
+            top_color: Some(top_color),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn with_luminance(color: Color) -> Self {
+        // https://en.wikipedia.org/wiki/Relative_luminance
+        let luminance = color.0.dot(vec3!(0.2126, 0.7152, 0.0722)).clamp(0.0, 1.0);
+        let index = (luminance * (LUMINANCE_RAMP.len() - 1) as f32).round() as usize;
+        Self {
+            text: Some(LUMINANCE_RAMP[index]),
+            ..Default::default()
+        }
+    }
+
+    // The edge goes along the surface, i.e. perpendicular to the normal projected on the screen
+    // where Y looks up
+    pub(crate) fn with_edge(normal: Vec2f) -> Self {
+        let (x, y) = (normal.x.abs(), normal.y.abs());
+        let text = if x > 2.0 * y {
+            '|'
+        } else if y > 2.0 * x {
+            '_'
+        } else if normal.x * normal.y > 0.0 {
+            '\\'
+        } else {
+            '/'
+        };
+        Self {
+            text: Some(text),
+            ..Default::default()
+        }
+    }
+
+    // Background color, foreground color, and the character to draw
+    pub(crate) fn appearance(&self) -> (Color, Color, char) {
+        match (self.text, self.top_color) {
+            (None, Some(top_color)) => (self.color, top_color, UPPER_HALF_BLOCK),
+            (text, _) => (self.color, self.color.inverted(), text.unwrap_or(' ')),
+        }
+    }
+}
+
+// Symbols are equal when they look the same on the terminal, i.e. after color quantization
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        self.color.to_rgb8() == other.color.to_rgb8()
+            && self.top_color.map(Color::to_rgb8) == other.top_color.map(Color::to_rgb8)
+            && self.text == other.text
     }
 }
@@ -0,0 +1,50 @@
+use std::fmt::{Display, Formatter, Result};
+
+#[derive(Clone, Copy, Default)]
+pub(crate) enum SymbolMode {
+    // One sample per symbol drawn as a background colored space
+    #[default]
+    Full,
+    // Two samples per symbol drawn as an upper half block with distinct foreground and
+    // background colors
+    HalfBlock,
+    // One sample per symbol drawn as a character of the luminance ramp or an edge without any
+    // colors
+    Ascii,
+}
+
+impl SymbolMode {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "full" => Some(SymbolMode::Full),
+            "half-block" => Some(SymbolMode::HalfBlock),
+            "ascii" => Some(SymbolMode::Ascii),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            SymbolMode::Full => "full",
+            SymbolMode::HalfBlock => "half-block",
+            SymbolMode::Ascii => "ascii",
+        }
+    }
+
+    pub(crate) fn has_colors(&self) -> bool {
+        !matches!(self, SymbolMode::Ascii)
+    }
+
+    pub(crate) fn samples_per_symbol(&self) -> usize {
+        match self {
+            SymbolMode::Full | SymbolMode::Ascii => 1,
+            SymbolMode::HalfBlock => 2,
+        }
+    }
+}
+
+impl Display for SymbolMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str(self.name())
+    }
+}
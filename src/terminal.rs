@@ -0,0 +1,216 @@
+use std::{
+    io::{Write, stdout},
+    mem::MaybeUninit,
+    panic::{set_hook, take_hook},
+    ptr::null_mut,
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use libc::{
+    SIG_DFL, SIG_UNBLOCK, SIGINT, SIGTERM, SIGTSTP, STDIN_FILENO, STDOUT_FILENO, TCSANOW, VMIN,
+    VTIME, c_int, cfmakeraw, isatty, pthread_sigmask, raise, read, sigaddset, sigemptyset,
+    sighandler_t, signal, sigset_t, tcgetattr, tcsetattr, termios, write,
+};
+
+use crate::escape::Escape;
+
+// Ends a sixel DCS or a kitty APC that a signal may interrupt, otherwise the rest of the restore
+// sequence would be swallowed as a part of it
+const STRING_TERMINATOR: &str = "\x1b\\";
+// Longer responses are not expected from any query
+const MAX_RESPONSE_LEN: usize = 256;
+// In tenths of a second, how long to wait for every byte of the response
+const RESPONSE_TIMEOUT: u8 = 2;
+
+// Terminal attributes from before the raw mode
+static ORIGINAL_TERMIOS: OnceLock<termios> = OnceLock::new();
+// Escapes are encoded in advance, so that signal handlers only write the bytes
+static ENTER_SEQUENCE: OnceLock<String> = OnceLock::new();
+static RESTORE_SEQUENCE: OnceLock<String> = OnceLock::new();
+// Set once the terminal is restored for good, so that frames still queued are not written over
+// what is left on the terminal
+static IS_RESTORED: AtomicBool = AtomicBool::new(false);
+// Set when the program is resumed after being suspended, since the terminal may have been used
+// by something else in between
+static IS_REDRAW_NEEDED: AtomicBool = AtomicBool::new(false);
+
+// Keeps the terminal in raw mode and puts it back the way it was when dropped, on panic, on
+// SIGINT and SIGTERM, and for the time the program is suspended with SIGTSTP
+pub(crate) struct TerminalGuard;
+
+impl TerminalGuard {
+    pub(crate) fn new() -> Self {
+        let mut original_termios: termios = unsafe { MaybeUninit::zeroed().assume_init() };
+        unsafe { tcgetattr(STDIN_FILENO, &mut original_termios as *mut _) };
+        let _ = ORIGINAL_TERMIOS.set(original_termios);
+        let _ = ENTER_SEQUENCE.set(
+            [Escape::EnterAlternateScreen, Escape::EnableMouseTracking]
+                .map(|escape| escape.encode())
+                .concat(),
+        );
+        // A frame may be cut off in the middle of a synchronized update, which would leave the
+        // terminal frozen
+        let escapes = [
+            Escape::EndSynchronizedUpdate,
+            Escape::DisableMouseTracking,
+            Escape::ResetAttributes,
+            Escape::MakeCursorVisible,
+            Escape::LeaveAlternateScreen,
+        ];
+        let _ = RESTORE_SEQUENCE.set(format!(
+            "{STRING_TERMINATOR}{}",
+            escapes.map(|escape| escape.encode()).concat()
+        ));
+        enter();
+        let default_hook = take_hook();
+        set_hook(Box::new(move |info| {
+            restore_after_output();
+            default_hook(info);
+        }));
+        unsafe {
+            signal(SIGINT, handle_termination as *const () as sighandler_t);
+            signal(SIGTERM, handle_termination as *const () as sighandler_t);
+            signal(SIGTSTP, handle_suspension as *const () as sighandler_t);
+        }
+        Self
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_after_output();
+    }
+}
+
+// In raw mode Ctrl-Z is read as a key, so the program suspends itself the same way the terminal
+// would. The handler runs on this thread before raise returns, and stdout is held locked until
+// then, so the output thread finishes its frame first and doesn't write while suspended.
+pub(crate) fn suspend() {
+    let _stdout = stdout().lock();
+    unsafe { raise(SIGTSTP) };
+}
+
+// Tells if the terminal content may have changed since the last time it was asked
+pub(crate) fn take_redraw_request() -> bool {
+    IS_REDRAW_NEEDED.swap(false, Ordering::Relaxed)
+}
+
+// Frames are written with stdout locked, so the one being written is finished first, and the
+// ones after it are dropped. Signal handlers can't wait for it, so they restore right away.
+fn restore_after_output() {
+    let _stdout = stdout().lock();
+    IS_RESTORED.store(true, Ordering::Relaxed);
+    restore();
+}
+
+// Expected to be checked with stdout locked, the same way it is set
+pub(crate) fn is_restored() -> bool {
+    IS_RESTORED.load(Ordering::Relaxed)
+}
+
+// Only async-signal-safe functions are used to be callable from signal handlers
+fn restore() {
+    if let Some(original_termios) = ORIGINAL_TERMIOS.get() {
+        unsafe { tcsetattr(STDIN_FILENO, TCSANOW, original_termios as *const _) };
+    }
+    if let Some(sequence) = RESTORE_SEQUENCE.get() {
+        write_unbuffered(sequence);
+    }
+}
+
+// Raw mode on the alternate screen, so that the primary screen is left intact, with the mouse
+// reported as input
+fn enter() {
+    if let Some(original_termios) = ORIGINAL_TERMIOS.get() {
+        let mut raw_termios = *original_termios;
+        unsafe { cfmakeraw(&mut raw_termios as *mut _) };
+        unsafe { tcsetattr(STDIN_FILENO, TCSANOW, &raw_termios as *const _) };
+    }
+    if let Some(sequence) = ENTER_SEQUENCE.get() {
+        write_unbuffered(sequence);
+    }
+}
+
+fn write_unbuffered(sequence: &str) {
+    unsafe { write(STDOUT_FILENO, sequence.as_ptr() as *const _, sequence.len()) };
+}
+
+// Restores the terminal and terminates with the default action of the signal, which is delivered
+// as soon as the handler returns
+extern "C" fn handle_termination(signal_number: c_int) {
+    restore();
+    unsafe {
+        signal(signal_number, SIG_DFL);
+        raise(signal_number);
+    }
+}
+
+// https://www.gnu.org/software/libc/manual/html_node/Signaling-Yourself.html
+//
+// Stops with the default action right inside the handler, so the code after it runs on SIGCONT
+extern "C" fn handle_suspension(_: c_int) {
+    restore();
+    unsafe {
+        signal(SIGTSTP, SIG_DFL);
+        // The signal is blocked while its handler runs, and the mask is restored on return
+        let mut mask: sigset_t = MaybeUninit::zeroed().assume_init();
+        sigemptyset(&mut mask);
+        sigaddset(&mut mask, SIGTSTP);
+        pthread_sigmask(SIG_UNBLOCK, &mask, null_mut());
+        raise(SIGTSTP);
+        signal(SIGTSTP, handle_suspension as *const () as sighandler_t);
+    }
+    enter();
+    IS_REDRAW_NEEDED.store(true, Ordering::Relaxed);
+}
+
+// DECRQM reports whether the mode is set or reset when it is recognized, and the primary device
+// attributes that follow are answered by every terminal, so there is no need to wait for the
+// timeout
+// https://vt100.net/docs/vt510-rm/DECRQM.html
+// https://gist.github.com/christianparpart/d8a62cc1ab659194337d73e399004036
+pub(crate) fn is_synchronized_update_supported() -> bool {
+    query("\x1b[?2026$p\x1b[c", b'c').is_some_and(|response| {
+        [b"\x1b[?2026;1$y", b"\x1b[?2026;2$y"].iter().any(|report| {
+            response
+                .windows(report.len())
+                .any(|window| window == *report)
+        })
+    })
+}
+
+// Writes the request and reads the terminal response up to and including the terminator byte.
+// The terminal is in raw mode only for the time of the query. Terminals that do not understand
+// the request send nothing, so None is returned after the timeout.
+pub(crate) fn query(request: &str, terminator: u8) -> Option<Vec<u8>> {
+    if unsafe { isatty(STDIN_FILENO) } == 0 {
+        return None;
+    }
+    let mut old_termios: termios = unsafe { MaybeUninit::zeroed().assume_init() };
+    unsafe { tcgetattr(STDIN_FILENO, &mut old_termios as *mut _) };
+    let mut new_termios = old_termios;
+    unsafe { cfmakeraw(&mut new_termios as *mut _) };
+    new_termios.c_cc[VMIN] = 0;
+    new_termios.c_cc[VTIME] = RESPONSE_TIMEOUT;
+    unsafe { tcsetattr(STDIN_FILENO, TCSANOW, &new_termios as *const _) };
+    let mut stdout = stdout();
+    let _ = stdout.write_all(request.as_bytes());
+    let _ = stdout.flush();
+    let mut response = vec![];
+    let response = loop {
+        let mut byte = 0;
+        let read_count = unsafe { read(STDIN_FILENO, &mut byte as *mut u8 as *mut _, 1) };
+        if read_count != 1 || response.len() >= MAX_RESPONSE_LEN {
+            break None;
+        }
+        response.push(byte);
+        if byte == terminator {
+            break Some(response);
+        }
+    };
+    unsafe { tcsetattr(STDIN_FILENO, TCSANOW, &old_termios as *const _) };
+    response
+}
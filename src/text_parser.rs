@@ -0,0 +1,162 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::{
+    color::Color,
+    math::vec3::{Vec3f, vec3},
+};
+
+#[derive(Debug)]
+pub(crate) struct ParseError {
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+    pub(crate) message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!(
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        ))
+    }
+}
+
+impl Error for ParseError {}
+
+// Whitespace separated tokens of a single line along with their 1-based columns
+pub(crate) struct LineTokens<'a> {
+    line: usize,
+    end_column: usize,
+    tokens: Vec<(usize, &'a str)>,
+    position: usize,
+}
+
+impl<'a> LineTokens<'a> {
+    pub(crate) fn new(line_index: usize, text: &'a str) -> Self {
+        let mut tokens = vec![];
+        let mut start: Option<usize> = None;
+        let mut column = 0;
+        for (index, char) in text.char_indices() {
+            column += 1;
+            match (char.is_whitespace(), start) {
+                (false, None) => start = Some(index),
+                (true, Some(token_start)) => {
+                    let token = &text[token_start..index];
+                    tokens.push((column - token.chars().count(), token));
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(token_start) = start {
+            let token = &text[token_start..];
+            tokens.push((column + 1 - token.chars().count(), token));
+        }
+        Self {
+            line: line_index + 1,
+            end_column: column + 1,
+            tokens,
+            position: 0,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    pub(crate) fn error(&self, column: usize, message: String) -> ParseError {
+        ParseError {
+            line: self.line,
+            column,
+            message,
+        }
+    }
+
+    // Column of the next token, or of the line end when there are no more tokens
+    fn next_column(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end_column, |&(column, _)| column)
+    }
+
+    pub(crate) fn next(&mut self, expected: &str) -> Result<(usize, &'a str), ParseError> {
+        let Some(&token) = self.tokens.get(self.position) else {
+            return Err(self.error(
+                self.end_column,
+                format!("expected {expected}, found end of line"),
+            ));
+        };
+        self.position += 1;
+        Ok(token)
+    }
+
+    pub(crate) fn next_f32(&mut self, expected: &str) -> Result<f32, ParseError> {
+        let (column, token) = self.next(expected)?;
+        token
+            .parse()
+            .map_err(|_| self.error(column, format!("expected {expected}, found \"{token}\"")))
+    }
+
+    // Numbers that parse but make no sense where they are used are reported too
+    pub(crate) fn next_f32_where<F>(
+        &mut self,
+        expected: &str,
+        requirement: &str,
+        is_valid: F,
+    ) -> Result<f32, ParseError>
+    where
+        F: Fn(f32) -> bool,
+    {
+        let column = self.next_column();
+        let value = self.next_f32(expected)?;
+        if !is_valid(value) {
+            return Err(self.error(
+                column,
+                format!("expected {expected} {requirement}, found {value}"),
+            ));
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn next_vec3(&mut self, expected: &str) -> Result<Vec3f, ParseError> {
+        let x = self.next_f32(expected)?;
+        let y = self.next_f32(expected)?;
+        let z = self.next_f32(expected)?;
+        Ok(vec3!(x, y, z))
+    }
+
+    // Directions are normalized, which is impossible for zero vectors
+    pub(crate) fn next_direction(&mut self, expected: &str) -> Result<Vec3f, ParseError> {
+        let column = self.next_column();
+        let direction = self.next_vec3(expected)?;
+        if direction.length() == 0.0 {
+            return Err(self.error(column, format!("expected {expected}, found zero vector")));
+        }
+        Ok(direction.normalize())
+    }
+
+    // Colors are written in the CSS hex notation, e.g. #ff8000
+    pub(crate) fn next_color(&mut self, expected: &str) -> Result<Color, ParseError> {
+        let (column, token) = self.next(expected)?;
+        token
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .map(Color::from_hex)
+            .ok_or_else(|| self.error(column, format!("expected {expected}, found \"{token}\"")))
+    }
+
+    pub(crate) fn finish(&self) -> Result<(), ParseError> {
+        match self.tokens.get(self.position) {
+            Some((column, token)) => Err(self.error(*column, format!("unexpected \"{token}\""))),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,6 +1,7 @@
-use crate::{Color, trace_stats::TraceStats};
+use crate::{Color, math::vec3::Vec3f};
 
 pub(crate) struct TracePayload {
     pub(crate) color: Color,
-    pub(crate) stats: TraceStats,
+    // Normal of the surface the initial ray hit, if any
+    pub(crate) normal: Option<Vec3f>,
 }
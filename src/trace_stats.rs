@@ -10,6 +10,8 @@ pub(crate) struct TraceStats {
     pub(crate) hit: usize,
     pub(crate) shadow_traced: usize,
     pub(crate) shadow_hit: usize,
+    // Shadow rays traced towards every scene light in addition to the sun
+    pub(crate) light_shadow_traced: Vec<usize>,
 }
 
 impl AddAssign for TraceStats {
@@ -19,6 +21,17 @@ impl AddAssign for TraceStats {
         self.hit += rhs.hit;
         self.shadow_traced += rhs.shadow_traced;
         self.shadow_hit += rhs.shadow_hit;
+        if self.light_shadow_traced.len() < rhs.light_shadow_traced.len() {
+            self.light_shadow_traced
+                .resize(rhs.light_shadow_traced.len(), 0);
+        }
+        for (count, rhs_count) in self
+            .light_shadow_traced
+            .iter_mut()
+            .zip(rhs.light_shadow_traced)
+        {
+            *count += rhs_count;
+        }
     }
 }
 
@@ -27,6 +40,10 @@ impl Display for TraceStats {
         f.write_fmt(format_args!(
             "{} rays ({} reflected, {} hit), {} shadow rays ({} hit)",
             self.traced, self.reflected, self.hit, self.shadow_traced, self.shadow_hit
-        ))
+        ))?;
+        if !self.light_shadow_traced.is_empty() {
+            f.write_fmt(format_args!(", per light {:?}", self.light_shadow_traced))?;
+        }
+        Ok(())
     }
 }
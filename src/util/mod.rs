@@ -1,9 +1,11 @@
 pub(crate) mod average_sum;
+pub(crate) mod pcg;
+pub(crate) mod thread_pool;
 pub(crate) mod timer;
 
 use std::ops::{Add, Mul, Sub};
 
-use libc::{RAND_MAX, rand};
+pub(crate) use pcg::random;
 
 #[cfg(test)]
 use crate::consts::EPSILON;
@@ -15,9 +17,10 @@ where
     a + (b - a) * ratio
 }
 
-pub(crate) fn random() -> f32 {
-    let rand = unsafe { rand() };
-    rand as f32 / RAND_MAX as f32
+// https://registry.khronos.org/OpenGL-Refpages/gl4/html/smoothstep.xhtml
+pub(crate) fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
 }
 
 #[cfg(test)]
@@ -0,0 +1,98 @@
+use std::cell::Cell;
+
+const MULTIPLIER: u64 = 6364136223846793005;
+// Every pixel gets its own seed on the same stream
+const STREAM: u64 = 0xda3e39cb94b95bdb;
+
+thread_local! {
+    static GENERATOR: Cell<Pcg32> = const { Cell::new(Pcg32::new(0, STREAM)) };
+}
+
+// https://www.pcg-random.org/download.html
+//
+// PCG32 (XSH RR) gives the same numbers on every platform, unlike libc rand(), and is cheap to
+// seed, so every pixel of every frame gets its own reproducible sequence.
+#[derive(Clone, Copy)]
+pub(crate) struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+impl Pcg32 {
+    pub(crate) const fn new(seed: u64, stream: u64) -> Self {
+        let mut generator = Self {
+            state: 0,
+            increment: stream << 1 | 1,
+        };
+        generator.step();
+        generator.state = generator.state.wrapping_add(seed);
+        generator.step();
+        generator
+    }
+
+    const fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(MULTIPLIER)
+            .wrapping_add(self.increment);
+    }
+
+    pub(crate) fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.step();
+        let xorshifted = ((state >> 18 ^ state) >> 27) as u32;
+        let rotation = (state >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+}
+
+// Reseeds the generator of the current thread. Neighbor pixels and frames get unrelated
+// sequences because the inputs are mixed with SplitMix64 first.
+pub(crate) fn seed(seed: u64, frame_index: u64, pixel_index: u64) {
+    let mixed = split_mix_64(split_mix_64(split_mix_64(seed) ^ frame_index) ^ pixel_index);
+    GENERATOR.set(Pcg32::new(mixed, STREAM));
+}
+
+// In the range of [0, 1)
+pub(crate) fn random() -> f32 {
+    let mut generator = GENERATOR.get();
+    let value = generator.next_u32();
+    GENERATOR.set(generator);
+    // 24 bits fit the f32 mantissa exactly
+    (value >> 8) as f32 / (1 << 24) as f32
+}
+
+// https://prng.di.unimi.it/splitmix64.c
+fn split_mix_64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ z >> 30).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ z >> 27).wrapping_mul(0x94d049bb133111eb);
+    z ^ z >> 31
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Pcg32, random, seed};
+
+    #[test]
+    fn test() {
+        // Output of the reference pcg32-demo seeded with 42 and 54
+        let mut generator = Pcg32::new(42, 54);
+        let values: Vec<u32> = (0..6).map(|_| generator.next_u32()).collect();
+        assert_eq!(
+            values,
+            [
+                0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e
+            ]
+        );
+        seed(1, 2, 3);
+        let a: Vec<f32> = (0..4).map(|_| random()).collect();
+        seed(1, 2, 3);
+        let b: Vec<f32> = (0..4).map(|_| random()).collect();
+        seed(1, 2, 4);
+        let c: Vec<f32> = (0..4).map(|_| random()).collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(a.iter().all(|value| (0.0..1.0).contains(value)));
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    mem::transmute,
+    panic::{AssertUnwindSafe, catch_unwind},
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread::{JoinHandle, spawn},
+};
+
+// Items are split into tiles small enough to balance the load, and big enough that pulling them
+// costs nothing compared to tracing
+const TILE_LEN: usize = 64;
+
+type Job = &'static (dyn Fn() + Sync);
+
+#[derive(Default)]
+struct State {
+    job: Option<Job>,
+    // Tells the workers apart a new job from the one they have just finished
+    generation: usize,
+    active_count: usize,
+    has_panicked: bool,
+    is_stopping: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    // Notified when a job is given or the pool stops
+    job_given: Condvar,
+    // Notified when a worker is done with the job
+    job_done: Condvar,
+}
+
+// Threads live as long as the pool, so nothing is spawned per frame. Tasks are pulled one by one
+// from an atomic counter, which balances the load when some tasks are much more expensive than
+// others.
+pub(crate) struct ThreadPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    pub(crate) fn new(thread_count: usize) -> Self {
+        let shared = Arc::new(Shared::default());
+        let workers = (0..thread_count.max(1))
+            .map(|_| {
+                let shared = shared.clone();
+                spawn(move || work(&shared))
+            })
+            .collect();
+        Self { shared, workers }
+    }
+
+    // Calls the task for every index from 0 to the task count on the pool threads and returns when
+    // all of them are done. The pool is borrowed mutably, so only one job runs at a time.
+    pub(crate) fn run<F>(&mut self, task_count: usize, task: F)
+    where
+        F: Fn(usize) + Sync,
+    {
+        let next_task_index = AtomicUsize::new(0);
+        let job = || {
+            loop {
+                let task_index = next_task_index.fetch_add(1, Ordering::Relaxed);
+                if task_index >= task_count {
+                    break;
+                }
+                task(task_index);
+            }
+        };
+        let job: &(dyn Fn() + Sync) = &job;
+        // SAFETY: Workers drop the job reference before reporting that they are done, and this
+        // function does not return until every worker has reported, so the job outlives its uses.
+        // No other run can replace the job or reset the count in between, since it would need
+        // the pool borrowed mutably too.
+        let job: Job = unsafe { transmute::<&(dyn Fn() + Sync), Job>(job) };
+        let mut state = self.shared.state.lock().unwrap();
+        state.job = Some(job);
+        state.generation += 1;
+        state.active_count = self.workers.len();
+        self.shared.job_given.notify_all();
+        while state.active_count > 0 {
+            state = self.shared.job_done.wait(state).unwrap();
+        }
+        state.job = None;
+        if state.has_panicked {
+            state.has_panicked = false;
+            panic!("thread pool task panicked");
+        }
+    }
+
+    // Gives every thread disjoint tiles of the items along with the index of the first item of
+    // the tile
+    pub(crate) fn run_over_tiles<T, F>(&mut self, items: &mut [T], task: F)
+    where
+        T: Send,
+        F: Fn(usize, &mut [T]) + Sync,
+    {
+        // Every tile is locked only once by the thread that pulled its index
+        let tiles: Vec<Mutex<&mut [T]>> = items.chunks_mut(TILE_LEN).map(Mutex::new).collect();
+        self.run(tiles.len(), |tile_index| {
+            let mut tile = tiles[tile_index].lock().unwrap();
+            task(tile_index * TILE_LEN, &mut tile);
+        });
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().is_stopping = true;
+        self.shared.job_given.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work(shared: &Shared) {
+    let mut generation = 0;
+    loop {
+        let job = {
+            let mut state = shared.state.lock().unwrap();
+            while !state.is_stopping && state.generation == generation {
+                state = shared.job_given.wait(state).unwrap();
+            }
+            if state.is_stopping {
+                return;
+            }
+            generation = state.generation;
+            state.job
+        };
+        // A panicking task must not leave the pool waiting forever
+        let has_panicked = job.is_some_and(|job| catch_unwind(AssertUnwindSafe(job)).is_err());
+        let mut state = shared.state.lock().unwrap();
+        state.has_panicked |= has_panicked;
+        state.active_count -= 1;
+        if state.active_count == 0 {
+            shared.job_done.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::ThreadPool;
+
+    #[test]
+    fn test() {
+        let mut pool = ThreadPool::new(4);
+        for task_count in [0, 1, 1000] {
+            let counts: Vec<AtomicUsize> = (0..task_count).map(|_| AtomicUsize::new(0)).collect();
+            pool.run(task_count, |index| {
+                counts[index].fetch_add(1, Ordering::Relaxed);
+            });
+            assert!(
+                counts
+                    .iter()
+                    .all(|count| count.load(Ordering::Relaxed) == 1)
+            );
+        }
+        let mut items = vec![0; 1000];
+        pool.run_over_tiles(&mut items, |start_index, tile| {
+            for (offset, item) in tile.iter_mut().enumerate() {
+                *item = start_index + offset;
+            }
+        });
+        assert!(items.iter().enumerate().all(|(index, item)| index == *item));
+    }
+}
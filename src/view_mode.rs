@@ -10,6 +10,16 @@ pub(crate) enum ViewMode {
 }
 
 impl ViewMode {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "color" => Some(ViewMode::Color),
+            "normal" => Some(ViewMode::Normal),
+            "depth" => Some(ViewMode::Depth),
+            "complexity" => Some(ViewMode::Complexity),
+            _ => None,
+        }
+    }
+
     fn name(&self) -> &'static str {
         match self {
             ViewMode::Color => "color",